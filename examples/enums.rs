@@ -11,21 +11,21 @@ where
     T: std::fmt::Debug,
 {
     #[error_message(&format!("Couldn't serialize data: {:?}", unnamed0))]
-    SerializeError(T),
+    Serialize(T),
     #[error_message("Couldn't deserialize data")]
-    DeserializeError,
+    Deserialize,
     #[error_message(inner)]
-    FooError {
+    Foo {
         inner: &'static str,
     },
     // Will have default message
-    BarError,
+    Bar,
 }
 
 fn main() {
     let from_string = from_str::<String>("")
         .report()
-        .change_context(MainError::<()>::DeserializeError)
+        .change_context(MainError::<()>::Deserialize)
         .attach_printable_lazy(|| r#"Data: """#);
 
     assert!(from_string.is_err());
@@ -35,15 +35,15 @@ fn main() {
 
     let to_string = to_string(&map)
         .report()
-        .change_context(MainError::SerializeError(map));
+        .change_context(MainError::Serialize(map));
 
     assert!(to_string.is_err());
     println!("{:#?}", to_string.err().unwrap());
 
     println!(
         "{:#?}",
-        Report::new(MainError::<()>::FooError { inner: "hello" })
+        Report::new(MainError::<()>::Foo { inner: "hello" })
     );
 
-    println!("{:#?}", Report::new(MainError::<()>::BarError));
+    println!("{:#?}", Report::new(MainError::<()>::Bar));
 }
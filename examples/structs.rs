@@ -11,11 +11,11 @@ struct FooError {
 }
 
 fn main() {
-    let foo = read_to_string("foo.txt")
+    let result = read_to_string("foo.txt")
         .report()
         .change_context(FooError { bar: 0, baz: 1 });
 
-    assert!(foo.is_err());
-    let err = foo.err().unwrap();
+    assert!(result.is_err());
+    let err = result.err().unwrap();
     println!("{:?}\n{:?}", err, err.downcast_ref::<FooError>().unwrap())
 }
@@ -0,0 +1,95 @@
+//! The fields bound by the match arms writing the messages of variants
+
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use syn::{ext::IdentExt, Fields, Ident, Lit};
+
+use crate::unnamed_ident;
+
+/// The pattern of a variant with `fields` binding the ones `message`
+/// references, the others are `_` or left to `..`. `format` tells whether
+/// `message` is the arguments of a format macro, its string literals can
+/// then capture fields
+///
+/// `Tuple(u8, u8)` with the message `"{1}"` is matched as
+/// `Self::Tuple(_, unnamed1)` and `Named { a: u8, b: u8 }` with
+/// `&format!("{a}")` as `Self::Named { a, .. }`
+pub(crate) fn pattern(fields: &Fields, message: &TokenStream2, format: bool) -> TokenStream2 {
+    match fields {
+        Fields::Named(fields) => {
+            let names = fields
+                .named
+                .iter()
+                .filter_map(|field| field.ident.as_ref())
+                .filter(|name| references(message.to_owned(), name, format))
+                .collect::<Vec<_>>();
+            match names.len() == fields.named.len() {
+                true => quote!({ #(#names),* }),
+                false => quote!({ #(#names,)* .. }),
+            }
+        }
+        Fields::Unnamed(fields) => {
+            let bindings = (0..fields.unnamed.len()).map(|pos| {
+                let name = unnamed_ident(pos);
+                match references(message.to_owned(), &name, format) {
+                    true => quote!(#name),
+                    false => quote!(_),
+                }
+            });
+            quote!((#(#bindings),*))
+        }
+        Fields::Unit => quote!(),
+    }
+}
+
+/// Whether `tokens` may use the binding `name`, as an identifier other
+/// than a field accessed through a `.` or, in the arguments of a macro, as
+/// an argument captured by a `{name}` placeholder of a string literal
+fn references(tokens: TokenStream2, name: &Ident, in_macro: bool) -> bool {
+    let name = name.unraw();
+    // The dots right before the current token, two of them are a range
+    let mut dots = 0;
+    let mut after_bang = false;
+    for token in tokens {
+        let found = match &token {
+            TokenTree::Group(group) => references(group.stream(), &name, in_macro || after_bang),
+            TokenTree::Ident(ident) => dots != 1 && ident.unraw() == name,
+            TokenTree::Literal(literal) if in_macro => match Lit::new(literal.to_owned()) {
+                Lit::Str(lit) => captures(&lit.value(), &name.to_string()),
+                _ => false,
+            },
+            TokenTree::Literal(_) | TokenTree::Punct(_) => false,
+        };
+        if found {
+            return true;
+        }
+        dots = match &token {
+            TokenTree::Punct(punct) if punct.as_char() == '.' => dots + 1,
+            _ => 0,
+        };
+        after_bang = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '!');
+    }
+    false
+}
+
+/// Whether the format string `value` has a `{name}` placeholder, possibly
+/// with a format spec
+fn captures(value: &str, name: &str) -> bool {
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '{' {
+            continue;
+        }
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            continue;
+        }
+
+        let placeholder = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+        let argument = placeholder.split(':').next().unwrap_or_default();
+        if argument.trim() == name {
+            return true;
+        }
+    }
+    false
+}
@@ -0,0 +1,117 @@
+//! Handling of the `#[error_code(...)]` attribute and of the
+//! `discriminant_code` and `prefix_with_code` options
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, TokenStreamExt};
+use syn::{
+    punctuated::Punctuated, Attribute, Error, Fields, Generics, Ident, LitInt, Meta, NestedMeta,
+    Result, Token, Variant,
+};
+
+use crate::{cfgs, message_attr, options::Options, MessageMeta};
+
+/// The code out of `#[error_code(1001)]` or
+/// `#[error_message(code = 1001, ...)]`, a `u32`
+pub(crate) fn error_code(attrs: &[Attribute]) -> Result<Option<LitInt>> {
+    let mut code = match message_attr(attrs) {
+        Some(attr) => MessageMeta::from_attr(attr)?.and_then(|meta| meta.code),
+        None => None,
+    };
+
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("error_code")) {
+        if code.is_some() {
+            return Err(Error::new_spanned(attr, "duplicate error code"));
+        }
+
+        let lit: LitInt = attr.parse_args()?;
+        lit.base10_parse::<u32>()?;
+        code = Some(lit);
+    }
+
+    Ok(code)
+}
+
+/// Rejects `prefix_with_code` when there's no `code` method to call
+pub(crate) fn require_code(options: &Options, has_code: bool) -> Result<()> {
+    match (&options.prefix_with_code, has_code) {
+        (Some(prefix), false) => Err(Error::new_spanned(
+            prefix,
+            "prefix_with_code requires an #[error_code] or discriminant_code",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The integer type of the `#[repr(...)]` of an enum, like `u16` out of
+/// `#[repr(C, u16)]`
+pub(crate) fn repr_type(attrs: &[Attribute]) -> Result<Option<Ident>> {
+    const INTEGERS: [&str; 12] = [
+        "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+    ];
+
+    let mut repr = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("repr")) {
+        if let Meta::List(list) = attr.parse_meta()? {
+            for hint in list.nested {
+                if let NestedMeta::Meta(Meta::Path(path)) = hint {
+                    if INTEGERS.iter().any(|integer| path.is_ident(integer)) {
+                        repr = path.get_ident().cloned();
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(repr)
+}
+
+/// The body of the `code` method of a fieldless enum returning the
+/// discriminants of its variants, as a `ty`
+pub(crate) fn discriminant_code(
+    variants: &Punctuated<Variant, Token![,]>,
+    ty: &TokenStream2,
+) -> Result<TokenStream2> {
+    let mut arms = quote!();
+    for variant in variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                &variant.fields,
+                "discriminant_code requires every variant to be a unit variant",
+            ));
+        }
+
+        let cfgs = cfgs(&variant.attrs);
+        let ident = &variant.ident;
+        arms.append_all(quote!(#cfgs Self::#ident => Self::#ident as #ty,));
+    }
+
+    // Matching the place rather than the reference is exhaustive without
+    // arms, for enums without variants
+    Ok(quote! {
+        match *self {
+            #arms
+        }
+    })
+}
+
+/// The `code` method returning `body`, a `ty`, added to the inherent impl
+pub(crate) fn code_method(ty: TokenStream2, body: TokenStream2) -> TokenStream2 {
+    quote! {
+        /// The code of this error
+        pub fn code(&self) -> #ty {
+            #body
+        }
+    }
+}
+
+/// The conversion of a borrowed error into its code, a `ty`
+pub(crate) fn code_from_impl(ident: &Ident, generics: &Generics, ty: TokenStream2) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::std::convert::From<&#ident #ty_generics> for #ty #where_clause {
+            fn from(err: &#ident #ty_generics) -> #ty {
+                err.code()
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! The per-variant constructors of `#[error_stack(constructors)]` and
+//! predicates of `#[error_stack(predicates)]`
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, TokenStreamExt};
+use syn::{ext::IdentExt, punctuated::Punctuated, Error, Fields, Ident, Result, Token, Variant};
+
+use crate::{cfgs, unnamed_ident};
+
+/// A `#[track_caller]` constructor per variant, named after the variant in
+/// snake case and taking its fields in order. `methods` are the names
+/// already taken in the inherent impl, the constructors are added to them
+pub(crate) fn constructors(
+    variants: &Punctuated<Variant, Token![,]>,
+    methods: &mut Vec<String>,
+) -> Result<TokenStream2> {
+    let mut constructors = quote!();
+    for variant in variants {
+        let cfgs = cfgs(&variant.attrs);
+        let variant_ident = &variant.ident;
+        let fn_ident = constructor_ident(variant_ident)?;
+        take_name(methods, &fn_ident, variant_ident, "constructor")?;
+
+        let (params, build) = match &variant.fields {
+            Fields::Named(fields) => {
+                let names = fields.named.iter().map(|field| &field.ident);
+                let params = fields
+                    .named
+                    .iter()
+                    .map(|field| {
+                        let (name, ty) = (&field.ident, &field.ty);
+                        quote!(#name: #ty)
+                    })
+                    .collect::<Vec<_>>();
+                (params, quote!(Self::#variant_ident { #(#names),* }))
+            }
+            Fields::Unnamed(fields) => {
+                let names = (0..fields.unnamed.len())
+                    .map(unnamed_ident)
+                    .collect::<Vec<_>>();
+                let params = names
+                    .iter()
+                    .zip(&fields.unnamed)
+                    .map(|(name, field)| {
+                        let ty = &field.ty;
+                        quote!(#name: #ty)
+                    })
+                    .collect();
+                (params, quote!(Self::#variant_ident(#(#names),*)))
+            }
+            Fields::Unit => (Vec::new(), quote!(Self::#variant_ident)),
+        };
+
+        let doc = format!("Constructs [`Self::{variant_ident}`]");
+        constructors.append_all(quote! {
+            #[doc = #doc]
+            #cfgs
+            #[track_caller]
+            pub fn #fn_ident(#(#params),*) -> Self {
+                #build
+            }
+        });
+    }
+
+    Ok(constructors)
+}
+
+/// An `is_<variant>` method per variant, named after the variant in snake
+/// case, telling whether `self` is that variant. `methods` are the names
+/// already taken in the inherent impl, the predicates are added to them
+pub(crate) fn predicates(
+    variants: &Punctuated<Variant, Token![,]>,
+    methods: &mut Vec<String>,
+) -> Result<TokenStream2> {
+    let mut predicates = quote!();
+    for variant in variants {
+        let cfgs = cfgs(&variant.attrs);
+        let variant_ident = &variant.ident;
+        // `is_super` is an identifier even though `super` isn't
+        let fn_ident = format_ident!(
+            "is_{}",
+            snake_case(variant_ident),
+            span = variant_ident.span()
+        );
+        take_name(methods, &fn_ident, variant_ident, "predicate")?;
+
+        let doc = format!("Whether this is [`Self::{}`]", variant_ident.unraw());
+        predicates.append_all(quote! {
+            #[doc = #doc]
+            #cfgs
+            #[must_use]
+            pub fn #fn_ident(&self) -> bool {
+                ::std::matches!(self, Self::#variant_ident { .. })
+            }
+        });
+    }
+
+    Ok(predicates)
+}
+
+/// Adds the name of the generated method `fn_ident` to `methods`, unless
+/// another method is named like it, like the constructors of `IoError` and
+/// `IOError` or the one of `Kind` and `kind()`
+fn take_name(
+    methods: &mut Vec<String>,
+    fn_ident: &Ident,
+    variant_ident: &Ident,
+    method: &str,
+) -> Result<()> {
+    let name = fn_ident.unraw().to_string();
+    if methods.contains(&name) {
+        return Err(Error::new_spanned(
+            variant_ident,
+            format!(
+                "the {method} `{name}` of this variant clashes with another generated method, \
+                 rename the variant"
+            ),
+        ));
+    }
+
+    methods.push(name);
+    Ok(())
+}
+
+/// The constructor of the variant `ident`, its name in snake case, as a raw
+/// identifier when it's a keyword like `r#type`
+fn constructor_ident(ident: &Ident) -> Result<Ident> {
+    let snake = snake_case(ident);
+    // Not even raw identifiers can be named like these
+    if matches!(snake.as_str(), "super" | "self" | "crate") {
+        return Err(Error::new_spanned(
+            ident,
+            format!("the constructor name `{snake}` is not a valid identifier, rename the variant"),
+        ));
+    }
+
+    let mut fn_ident = syn::parse_str::<Ident>(&snake)
+        .or_else(|_| syn::parse_str::<Ident>(&format!("r#{snake}")))?;
+    fn_ident.set_span(ident.span());
+    Ok(fn_ident)
+}
+
+/// `NotFound` as `not_found` and `IOError` as `io_error`
+fn snake_case(ident: &Ident) -> String {
+    let name = ident.to_string();
+    let name = name.strip_prefix("r#").unwrap_or(&name);
+    let chars = name.chars().collect::<Vec<_>>();
+
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (pos, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && pos > 0 {
+            let prev = chars[pos - 1];
+            let next_is_lower = chars.get(pos + 1).is_some_and(|next| next.is_lowercase());
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower)
+            {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+
+    snake
+}
@@ -0,0 +1,90 @@
+//! Parsing and expansion of the [`errors!`](crate::errors) list of unit
+//! error structs
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, TokenStreamExt};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    punctuated::Punctuated,
+    Attribute, Data, DeriveInput, Ident, LitStr, Result, Token, Visibility,
+};
+
+use crate::{create_struct, options::Options};
+
+/// `Foo => "msg", Bar => "other"`, every entry optionally preceded by
+/// attributes and a visibility
+pub(crate) struct Errors(Punctuated<Entry, Token![,]>);
+
+struct Entry {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    message: LitStr,
+}
+
+impl Parse for Errors {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Punctuated::parse_terminated(input).map(Self)
+    }
+}
+
+impl Parse for Entry {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let ident = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let message = input.parse()?;
+
+        Ok(Self {
+            attrs,
+            vis,
+            ident,
+            message,
+        })
+    }
+}
+
+impl Errors {
+    /// A `#[derive(Debug)]` unit struct per entry along with the impls
+    /// `#[derive(ErrorStack)]` would generate for it
+    pub(crate) fn expand(self) -> Result<TokenStream2> {
+        let mut tokens = quote!();
+        for Entry {
+            attrs,
+            vis,
+            ident,
+            message,
+        } in self.0
+        {
+            let input: DeriveInput = parse_quote! {
+                #(#attrs)*
+                #[error_message(#message)]
+                #vis struct #ident;
+            };
+            let data = match input.data {
+                Data::Struct(data) => data,
+                _ => unreachable!("errors! only generates structs"),
+            };
+            let options = Options::from_attrs(&input.attrs)?;
+            let impls = create_struct(input.attrs, options, input.ident, input.generics, data)?;
+
+            // The helper attributes are only known to the derive
+            let attrs = attrs.iter().filter(|attr| {
+                !["error_message", "error_stack", "error_code"]
+                    .iter()
+                    .any(|helper| attr.path.is_ident(helper))
+            });
+            tokens.append_all(quote! {
+                #(#attrs)*
+                #[derive(::std::fmt::Debug)]
+                #vis struct #ident;
+
+                #impls
+            });
+        }
+
+        Ok(tokens)
+    }
+}
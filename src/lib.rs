@@ -18,7 +18,7 @@
 //! context, frames, etc. features, which to say the least are
 //! pretty cool and helpful for error handling & debugging.
 //!
-//! ```
+//! ```no_run
 //! use error_stack::{IntoReport, Result, ResultExt};
 //! use error_stack_derive::ErrorStack;
 //!
@@ -44,13 +44,13 @@
 //! infact you can use it with any other error handling crate,
 //! just like this
 //!
-//! ```
+//! ```no_run
 //! use error_stack_derive::ErrorStack;
 //!
 //! #[derive(ErrorStack, Debug)]
 //! #[error_message(&format!("An exception occured with foo: {}", self.0))]
-//!
 //! struct FooError(String);
+//!
 //! fn main() -> Result<(), FooError> {
 //!     let contents = std::fs::read_to_string("foo.txt").map_err(|e| FooError(e.to_string()))?;
 //!
@@ -114,11 +114,11 @@
 //! };
 //!
 //! impl std::fmt::Display for FooErrors {
-//!     fn fmt(&self, _____fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+//!     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 //!         match self {
-//!             Self::BarError => _____fmt.write_str(&format!("[{name}] An error occured; {:?}", name = "FooErrors", self)),
-//!             Self::BazError(unnamed0) => _____fmt.write_str(&format!("Error in baz ({unnamed0})")),
-//!             Self::QuxError { start, end } => _____fmt.write_str(&format!("Error in qux ({start}, {end})")),
+//!             Self::BarError => fmt.write_str(&format!("[{name}] An error occured; {:?}", self, name = "FooErrors")),
+//!             Self::BazError(unnamed0) => fmt.write_str(&format!("Error in baz ({unnamed0})")),
+//!             Self::QuxError { start, end } => fmt.write_str(&format!("Error in qux ({start}, {end})")),
 //!         }
 //!     }
 //! }
@@ -130,9 +130,27 @@
 //! you have a large code base and error handling definitely becomes dreadful.
 //! Read up the doc comments of [`ErrorStack`] for more information.
 //!
+mod bindings;
+mod code;
+mod constructors;
+mod errors;
+mod options;
+mod placeholders;
+mod source;
+#[cfg(test)]
+mod tests;
+
+use options::Options;
+use placeholders::Scanned;
 use proc_macro::TokenStream;
-use quote::{quote, TokenStreamExt};
-use syn::{parse, parse_str, Attribute, Data, DataEnum, DeriveInput, Fields, Generics, Ident};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, quote_spanned, TokenStreamExt};
+use source::{member, provided_fields, related_field, FromField, Source};
+use syn::{
+    ext::IdentExt, parse, parse::ParseStream, Attribute, Data, DataEnum, DataStruct, DeriveInput,
+    Error, Expr, Fields, Generics, Ident, Lit, LitInt, LitStr, Member, Meta, MetaNameValue, Result,
+    Token, Type, Variant, Visibility,
+};
 
 /// A derive-macro to easily create enums and structs compatible with
 /// error_stack. You can use a struct or an enum with it
@@ -142,7 +160,7 @@ use syn::{parse, parse_str, Attribute, Data, DataEnum, DeriveInput, Fields, Gene
 ///
 /// - When input cannot be passed as [`syn::DeriveInput`]
 /// - When the derive data is not one of [`syn::Data::Enum`] or
-/// [`syn::Data::Struct`]
+///   [`syn::Data::Struct`]
 ///
 ///
 /// ## Usage
@@ -152,11 +170,66 @@ use syn::{parse, parse_str, Attribute, Data, DataEnum, DeriveInput, Fields, Gene
 ///
 /// #[derive(ErrorStack, Debug)]
 /// // error_message tokens can be any token stream as long as it evaluates
-/// // to a &str
+/// // to something implementing `AsRef<str>`, a &str or a String. They are
+/// // evaluated lazily inside `Display::fmt`, every time the error is
+/// // formatted and never when it's constructed
 /// #[error_message("An error occured in Foo")]
 /// struct FooError;
 ///
 /// #[derive(ErrorStack, Debug)]
+/// // So an owned String works without borrowing it
+/// #[error_message(format!("Couldn't open {}", self.0))]
+/// struct OpenError(&'static str);
+///
+/// #[derive(ErrorStack, Debug)]
+/// // `{field}` placeholders of string literals are replaced with the
+/// // field, `{0}` for tuple structs, keeping their format spec. Literal
+/// // braces are escaped like in `format!`, `{{` and `}}`, and raw fields
+/// // like `r#type` are referenced without the prefix, `{type}`
+/// #[error_message("Range {start:#x}..{end:#x} is out of bounds")]
+/// struct RangeError {
+///     start: u64,
+///     end: u64,
+/// }
+///
+/// assert_eq!(
+///     RangeError { start: 16, end: 32 }.to_string(),
+///     "Range 0x10..0x20 is out of bounds"
+/// );
+///
+/// #[derive(ErrorStack, Debug)]
+/// // `name = expression` arguments after the literal are referenced like
+/// // fields, and so are the `name$` width and precision parameters
+/// #[error_message("{name:>width$}|", width = self.width + 2)]
+/// struct ColumnError {
+///     name: &'static str,
+///     width: usize,
+/// }
+///
+/// assert_eq!(
+///     ColumnError { name: "id", width: 4 }.to_string(),
+///     "    id|"
+/// );
+///
+/// #[derive(ErrorStack, Debug)]
+/// // Long messages can be split into comma separated string literals,
+/// // they are concatenated
+/// #[error_message(
+///     "The config file couldn't be found, ",
+///     "create one with `init`"
+/// )]
+/// struct MissingConfigError;
+///
+/// #[derive(ErrorStack, Debug)]
+/// // `debug` writes the [`std::fmt::Debug`] representation of the struct
+/// #[error_message(debug)]
+/// struct LimitError {
+///     limit: usize,
+/// }
+///
+/// assert_eq!(LimitError { limit: 3 }.to_string(), "LimitError { limit: 3 }");
+///
+/// #[derive(ErrorStack, Debug)]
 /// // The tokens are passed to the [`std::fmt::Formatter::write_str`]
 /// // method of the [`std::fmt::Formatter`] in the automatically
 /// // implemented Display impl. Passing an error message is mandatory
@@ -167,6 +240,14 @@ use syn::{parse, parse_str, Attribute, Data, DataEnum, DeriveInput, Fields, Gene
 /// where
 ///     A: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static;
 ///
+/// #[derive(ErrorStack, Debug)]
+/// // A reference to a string field is the cheapest message, it's written
+/// // as is without any formatting or allocation
+/// #[error_message(&self.message)]
+/// struct RemoteError {
+///     message: String,
+/// }
+///
 ///
 /// // And ofcourse enums are supported too
 /// #[derive(ErrorStack, Debug)]
@@ -174,155 +255,2897 @@ use syn::{parse, parse_str, Attribute, Data, DataEnum, DeriveInput, Fields, Gene
 /// // doesn't have a dedicated error message
 /// // When a default error message is not specified and an enum doesn't
 /// // have a dedicated message,
-/// // `&format!("[{name}] An error occured; {:?}", name = #struct_name, self)` is passed to
+/// // `format!("[{name}] An error occured; {:?}", self, name = #struct_name)` is passed to
 /// // [`std::fmt::Formatter::write_str`]
 /// #[error_message("Default error message")]
 /// enum EncoderError {
 ///     // For struct variants the name of the fields are left unchanged
 ///     // but for tuple variants they are named `unnamed{pos}`
+///     // and bound by reference, so a closure field bounded by `Fn` can be
+///     // called as `(unnamed0)()` but an `FnOnce` or `FnMut` one can't
 ///     #[error_message(&format!("Couldn't serialize data: {:?}", unnamed0))]
 ///     SerializeError(String),
 ///     DeserializeError,
+///     // `debug` writes the [`std::fmt::Debug`] representation of the
+///     // variant instead, which requires the enum to implement `Debug`
+///     #[error_message(debug)]
+///     InvalidInput { line: usize },
+///     // `display = field` forwards to the [`std::fmt::Display`] impl of
+///     // a field, by index for tuple variants, without allocating and
+///     // honoring the formatting flags
+///     #[error_message(display = 0)]
+///     Custom(std::fmt::Error),
+///     // `display_expr = expression` forwards to the `Display` impl of
+///     // any expression, like a method returning `impl Display`
+///     #[error_message(display_expr = unnamed0.escape_debug())]
+///     Escaped(String),
+///     // `pad(...)` writes the message with [`std::fmt::Formatter::pad`],
+///     // honoring the width and the alignment, which are ignored otherwise
+///     #[error_message(pad("Invalid encoding"))]
+///     InvalidEncoding,
+/// }
+///
+/// assert_eq!(
+///     EncoderError::InvalidInput { line: 3 }.to_string(),
+///     "InvalidInput { line: 3 }"
+/// );
+///
+/// #[derive(ErrorStack, Debug)]
+/// // The default error message is evaluated inside `Display::fmt` so,
+/// // just like struct messages, it can reference `self` which is the
+/// // variant that fell through
+/// #[error_message(&format!("Unhandled decoder error: {:?}", self))]
+/// enum DecoderError {
+///     #[error_message("Unexpected end of input")]
+///     Eof,
+///     InvalidByte(u8),
 /// }
+///
+/// assert_eq!(DecoderError::Eof.to_string(), "Unexpected end of input");
+/// assert_eq!(
+///     DecoderError::InvalidByte(0xff).to_string(),
+///     "Unhandled decoder error: InvalidByte(255)"
+/// );
 /// ```
-#[proc_macro_derive(ErrorStack, attributes(error_message))]
-pub fn error(tokens: TokenStream) -> TokenStream {
-    let DeriveInput {
-        attrs,
-        vis: _,
-        ident,
-        generics,
-        data,
-    } = parse(tokens).expect("derive input");
-
-    let ast = match data {
-        Data::Enum(data) => create_enum(attrs, ident, generics, data),
-        Data::Struct(_) => create_struct(attrs, ident, generics),
-        _ => panic!("#[derive(ErrorStack)] only supports structs and enums"),
-    };
-
-    ast.into()
-}
-
-fn create_enum(
-    attrs: Vec<Attribute>,
-    ident: Ident,
-    Generics {
-        lt_token,
-        params,
-        gt_token,
-        where_clause,
-    }: Generics,
-    DataEnum {
-        enum_token: _,
-        brace_token: _,
-        variants,
-    }: DataEnum,
-) -> TokenStream {
-    let message = match attrs
-        .iter()
-        .find(|attr| attr.path.is_ident("error_message"))
-    {
-        Some(attr) => attr.tokens.to_owned(),
-        None => {
-            let name = syn::LitStr::new(&ident.to_string(), ident.span());
-            quote!(&format!(
-                "[{name}] An error occured; {:?}",
-                self,
-                name = #name,
-            ))
-        }
-    };
-
-    let match_arms = {
-        let mut tmp = quote!();
-        tmp.append_all(variants.iter().filter_map(|variant| {
-            let ident = variant.ident.to_owned();
-            let message = variant.attrs.iter().find_map(|attr| {
-                if attr.path.is_ident("error_message") {
-                    return Some(attr.tokens.to_owned());
-                }
-                None
-            });
-
-            let additional = match variant.fields {
-                Fields::Named(ref named) => {
-                    let mut tmp = quote!();
-                    tmp.append_all(named.named.iter().map(|field| {
-                        let ident = field.ident.to_owned();
-                        quote! {
-                            #ident ,
-                        }
-                    }));
-                    quote! {{
-                        #tmp
-                    }}
-                }
-                Fields::Unnamed(ref unnamed) => {
-                    let mut tmp = quote!();
-                    tmp.append_all(unnamed.unnamed.iter().enumerate().map(|(pos, _)| {
-                        let ident: Ident = parse_str(&format!("unnamed{pos}")).unwrap();
-                        quote! {
-                            #ident ,
-                        }
-                    }));
-                    quote! {(#tmp)}
-                }
-                Fields::Unit => quote!(),
-            };
-
-            match message {
-                Some(tokens) => Some(quote! {
-                    Self::#ident #additional => _____fmt.write_str(#tokens),
-                }),
-                None => None,
-            }
-        }));
-        tmp
-    };
-
-    quote! {
-        impl #lt_token #params #gt_token std::fmt::Display for #ident #lt_token #params #gt_token #where_clause {
-            fn fmt(&self, _____fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                #[allow(unused_parens)]
-                match self {
-                    #match_arms
-                    _ => _____fmt.write_str(#message)
-                }
-            }
-        }
-
-        impl #lt_token #params #gt_token std::error::Error for #ident #lt_token #params #gt_token #where_clause {}
-    }
-    .into()
-}
-
-fn create_struct(
-    attrs: Vec<Attribute>,
-    ident: Ident,
-    Generics {
-        lt_token,
-        params,
-        gt_token,
-        where_clause,
-    }: Generics,
-) -> TokenStream {
-    let message = attrs
-        .iter()
-        .find(|attr| attr.path.is_ident("error_message"))
-        .expect("expected error message")
-        .tokens
-        .to_owned();
-
-    quote! {
-        impl #lt_token #params #gt_token std::fmt::Display for #ident #lt_token #params #gt_token #where_clause {
-            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                #[allow(unused_parens)]
-                fmt.write_str(#message)
-            }
-        }
-
-        impl #lt_token #params #gt_token std::error::Error for #ident #lt_token #params #gt_token #where_clause {}
-    }
-    .into()
+///
+/// Structs have no default message, deriving without one fails with a
+/// suggestion to add a message or to fall back to the [`std::fmt::Debug`]
+/// representation
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// struct MarkerError;
+/// ```
+///
+/// And that fallback requires the struct to implement [`std::fmt::Debug`],
+/// the error points at `debug`
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack)]
+/// #[error_message(debug)]
+/// struct MarkerError;
+/// ```
+///
+/// The default message of enums writes the [`std::fmt::Debug`]
+/// representation too, an enum with a variant falling back to it has to
+/// implement `Debug`, the error points at that variant
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack)]
+/// enum MarkerError {
+///     #[error_message("Marked")]
+///     Marked,
+///     Unmarked,
+/// }
+/// ```
+///
+/// That assertion comes along with the one of `assert_send_sync`, a
+/// `Debug` enum falling back to the default message still has to be
+/// `Send + Sync`
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(assert_send_sync)]
+/// enum MarkerError {
+///     #[error_message("Marked")]
+///     Marked(std::rc::Rc<()>),
+///     Unmarked,
+/// }
+/// ```
+///
+/// Placeholders have to name a field, the placeholders of the default
+/// message of an enum are rejected since there's no field to interpolate
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Value is {value}")]
+/// struct ValueError(u8);
+/// ```
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Value is {0}")]
+/// enum ValueError {
+///     Invalid(u8),
+/// }
+/// ```
+///
+/// A bare `#[error_message]` is rejected, on a struct as on a variant
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message]
+/// struct MarkerError;
+/// ```
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// enum MarkerError {
+///     #[error_message]
+///     Marker,
+/// }
+/// ```
+///
+/// Messages go on the struct, the enum or its variants, never on a field
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// struct FooError {
+///     #[error_message("An error occured in Foo")]
+///     bar: u8,
+/// }
+/// ```
+///
+///
+/// ## Sources
+///
+/// A field of a struct or of an enum variant can be marked with
+/// `#[source]` to be returned by [`std::error::Error::source`], its type
+/// has to implement [`std::error::Error`]. Marking the only field of a
+/// struct or of a variant with `#[from]` additionally implements [`From`]
+/// for the field's type, a `#[from]` field is the source unless another
+/// field is marked with `#[source]`
+///
+/// ```
+/// use std::error::Error;
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// enum ReadError {
+///     #[error_message("Couldn't read the file")]
+///     Io(#[from] std::io::Error),
+///     #[error_message(&format!("Invalid utf-8 at line {line}"))]
+///     Utf8 {
+///         line: usize,
+///         #[source]
+///         inner: std::str::Utf8Error,
+///     },
+/// }
+///
+/// let err = ReadError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+/// assert!(err.source().unwrap().is::<std::io::Error>());
+/// ```
+///
+/// Variants with more fields can still be converted into with
+/// `#[from(map = |source| ...)]`, the closure builds the variant out of
+/// the converted value
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// enum ParseError {
+///     #[error_message(&format!("Couldn't parse line {line}"))]
+///     Int {
+///         #[from(map = |source| Self::Int { source, line: 0 })]
+///         source: std::num::ParseIntError,
+///         line: usize,
+///     },
+/// }
+///
+/// let err = ParseError::from("a".parse::<u8>().unwrap_err());
+/// assert_eq!(err.to_string(), "Couldn't parse line 0");
+/// ```
+///
+/// With the `provide` feature, which requires a nightly compiler and
+/// `#![feature(error_generic_member_access)]`, errors with a source also
+/// forward [`std::error::Error::provide`] to it, so the data provided deeper
+/// in the chain, like backtraces, can be requested from the outer error.
+/// Fields marked with `#[provide]` are provided by reference first
+///
+/// Aggregate errors can mark a collection of boxed errors, like
+/// `Vec<Box<dyn Error + Send + Sync>>`, with `#[related]` which generates
+/// a `related(&self) -> impl Iterator<Item = &(dyn Error + 'static)>`
+/// method iterating over them
+///
+/// ```
+/// use std::error::Error;
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message(&format!("{} fields are invalid", self.errors.len()))]
+/// struct ValidationError {
+///     #[related]
+///     errors: Vec<Box<dyn Error + Send + Sync>>,
+/// }
+///
+/// let err = ValidationError {
+///     errors: vec!["a".parse::<u8>().unwrap_err().into()],
+/// };
+/// assert_eq!(err.related().count(), 1);
+/// ```
+///
+/// A struct or variant with a single field wrapping another error can be
+/// `#[error_message(transparent)]`, both [`std::fmt::Display`] and
+/// [`std::error::Error::source`] forward to the field, so the wrapper is
+/// invisible in the chain. The field can still be marked with `#[from]`,
+/// the [`From`] impl is generated and the source stays forwarded rather
+/// than being the field itself
+///
+/// ```
+/// use std::error::Error;
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// enum AppError {
+///     #[error_message(transparent)]
+///     Io(#[from] std::io::Error),
+/// }
+///
+/// let err = AppError::from(std::io::Error::other("disk full"));
+/// assert_eq!(err.to_string(), "disk full");
+/// assert!(err.source().is_none());
+/// ```
+///
+/// There has to be exactly one field to forward to
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message(transparent)]
+/// struct AppError(std::io::Error, usize);
+/// ```
+///
+///
+/// ## Error codes
+///
+/// A struct or a variant can declare a `u32` code with `#[error_code(...)]`,
+/// which generates a `code(&self) -> u32` method and a conversion of a
+/// borrowed error into its code, `u32::from(&err)`. Every variant needs a
+/// code, unless the enum declares a default one
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_code(500)]
+/// enum HttpError {
+///     #[error_message("Not found")]
+///     #[error_code(404)]
+///     NotFound,
+///     #[error_message("Internal error")]
+///     Internal,
+/// }
+///
+/// assert_eq!(HttpError::NotFound.code(), 404);
+/// let code: u32 = (&HttpError::Internal).into();
+/// assert_eq!(code, 500);
+/// ```
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// enum HttpError {
+///     #[error_message("Not found")]
+///     #[error_code(404)]
+///     NotFound,
+///     // Missing a code
+///     #[error_message("Internal error")]
+///     Internal,
+/// }
+/// ```
+///
+/// The message and the code can also be declared together in the
+/// key-value form of `#[error_message]`, taking `message` (or its alias
+/// `template`) or `display`, and `code`
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// enum HttpError {
+///     #[error_message(message = "Not found", code = 404)]
+///     NotFound,
+///     #[error_message(display = 0, code = 500)]
+///     Internal(std::fmt::Error),
+/// }
+///
+/// assert_eq!(HttpError::NotFound.to_string(), "Not found");
+/// assert_eq!(HttpError::NotFound.code(), 404);
+/// ```
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// // Unknown keys are rejected
+/// #[error_message(message = "Not found", status = 404)]
+/// struct NotFoundError;
+/// ```
+///
+/// Fieldless enums can instead return the discriminants of their variants
+/// as a code with `#[error_stack(discriminant_code)]`, an `i64` unless the
+/// enum has an integer `#[repr(...)]`
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(discriminant_code)]
+/// enum ExitError {
+///     #[error_message("Usage error")]
+///     Usage = 64,
+///     #[error_message("Data error")]
+///     Data = 65,
+/// }
+///
+/// assert_eq!(ExitError::Data.code(), 65);
+/// let code: i64 = (&ExitError::Usage).into();
+/// assert_eq!(code, 64);
+///
+/// #[derive(ErrorStack, Debug, Clone, Copy)]
+/// #[error_stack(discriminant_code)]
+/// #[repr(u8)]
+/// enum StatusError {
+///     #[error_message("Busy")]
+///     Busy = 1,
+/// }
+///
+/// let code: u8 = StatusError::Busy.code();
+/// assert_eq!(code, 1);
+/// ```
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(discriminant_code)]
+/// enum ExitError {
+///     #[error_message("Usage error")]
+///     Usage = 64,
+///     // Variants with fields can't be cast to their discriminant
+///     #[error_message("Io error")]
+///     Io(std::io::Error),
+/// }
+/// ```
+///
+/// ## Options
+///
+/// The generated impls can be configured with an `#[error_stack(...)]`
+/// attribute on the struct or enum, taking a comma separated list of
+/// options
+///
+/// ### `source_fn`
+///
+/// `#[error_stack(source_fn = my_source)]` implements
+/// [`std::error::Error::source`] by calling the inherent method
+/// `fn my_source(&self) -> Option<&(dyn std::error::Error + 'static)>`
+///
+/// ```
+/// use std::error::Error;
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Couldn't load the config")]
+/// #[error_stack(source_fn = cause)]
+/// struct ConfigError {
+///     causes: Vec<std::io::Error>,
+/// }
+///
+/// impl ConfigError {
+///     fn cause(&self) -> Option<&(dyn Error + 'static)> {
+///         self.causes.last().map(|err| err as _)
+///     }
+/// }
+///
+/// let err = ConfigError {
+///     causes: vec![std::io::ErrorKind::NotFound.into()],
+/// };
+/// assert!(err.source().is_some());
+/// ```
+///
+/// ### `explicit_source`
+///
+/// `#[error_stack(explicit_source)]` always implements
+/// [`std::error::Error::source`], returning `None` when there's no source
+/// field instead of relying on the default method, which makes the
+/// expanded code easier to read
+///
+/// ```
+/// use std::error::Error;
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Couldn't load the config")]
+/// #[error_stack(explicit_source)]
+/// struct ConfigError;
+///
+/// assert!(ConfigError.source().is_none());
+/// ```
+///
+/// ### `error_cfg`
+///
+/// `#[error_stack(error_cfg = "feature = \"std\"")]` gates the
+/// [`std::error::Error`] impl behind the given `cfg` predicate, along with
+/// the `display_chain` method walking its sources, while
+/// [`std::fmt::Display`] is always implemented
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Couldn't load the config")]
+/// #[error_stack(error_cfg = "feature = \"std\"")]
+/// struct ConfigError;
+///
+/// assert_eq!(ConfigError.to_string(), "Couldn't load the config");
+/// ```
+///
+/// ### `source_index`
+///
+/// `#[error_stack(source_index = 0)]` makes the field at that index of
+/// every tuple variant the source, unless another field is marked with
+/// `#[source]` or `#[from]`. The variants without such a field have no
+/// source
+///
+/// ```
+/// use std::error::Error;
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(source_index = 0)]
+/// enum ConfigError {
+///     #[error_message("Couldn't read the config")]
+///     Io(std::io::Error),
+///     #[error_message("Couldn't parse the config")]
+///     Parse(std::num::ParseIntError, usize),
+///     #[error_message("The config is empty")]
+///     Empty,
+/// }
+///
+/// let err = ConfigError::Io(std::io::ErrorKind::NotFound.into());
+/// assert!(err.source().is_some());
+/// assert!(ConfigError::Empty.source().is_none());
+/// ```
+///
+/// ### `legacy_cause`
+///
+/// `#[error_stack(legacy_cause)]` also implements the deprecated
+/// [`std::error::Error::cause`], returning the source, for the code still
+/// consulting it. It's only implemented when there's a source
+///
+/// ```
+/// use std::error::Error;
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(legacy_cause)]
+/// enum ConfigError {
+///     #[error_message("Couldn't read the config")]
+///     Io(#[source] std::io::Error),
+/// }
+///
+/// let err = ConfigError::Io(std::io::ErrorKind::NotFound.into());
+/// # #[allow(deprecated)]
+/// assert!(err.cause().is_some());
+/// ```
+///
+/// ### `assert_send_sync`
+///
+/// `#[error_stack(assert_send_sync)]` asserts at compile time that the
+/// error is `Send + Sync + 'static`, as required to be used as an
+/// `error_stack` context, so it fails at the definition rather than where
+/// it's used as a context
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Couldn't load the config")]
+/// #[error_stack(assert_send_sync)]
+/// struct ConfigError(std::sync::Arc<str>);
+/// ```
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Couldn't load the config")]
+/// #[error_stack(assert_send_sync)]
+/// struct ConfigError(std::rc::Rc<str>);
+/// ```
+///
+/// ### `rich_debug`
+///
+/// `#[error_stack(rich_debug)]` implements [`std::fmt::Debug`] by writing
+/// the name of the struct or of the variant along with the message, which
+/// reads better in reports than the fields. It conflicts with
+/// `#[derive(Debug)]`, which has to be removed
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack)]
+/// #[error_message(&format!("Bad input at line {}", self.line))]
+/// #[error_stack(rich_debug)]
+/// struct ParseError {
+///     line: usize,
+/// }
+///
+/// assert_eq!(
+///     format!("{:?}", ParseError { line: 3 }),
+///     r#"ParseError { display: "Bad input at line 3" }"#
+/// );
+/// ```
+///
+/// Since the Debug representation writes the message, the message can't
+/// write the Debug representation, with `debug` or the default message of
+/// enums
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack)]
+/// #[error_stack(rich_debug)]
+/// enum ParseError {
+///     Eof,
+/// }
+/// ```
+///
+/// ### `rename`
+///
+/// `#[error_stack(rename = "Validation")]` replaces the name of the type in
+/// the default message of enums and in the `json` messages of structs
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(rename = "Validation")]
+/// enum ValidationErrorInternal {
+///     Empty,
+/// }
+///
+/// assert_eq!(
+///     ValidationErrorInternal::Empty.to_string(),
+///     "[Validation] An error occured; Empty"
+/// );
+/// ```
+///
+/// ### `kind_enum`
+///
+/// `#[error_stack(kind_enum = ErrorKind)]` on an enum generates a sibling
+/// enum `ErrorKind` with the same variants and visibility but without their
+/// fields, along with a `kind(&self) -> ErrorKind` method, so consumers can
+/// match on the category of an error without depending on its fields
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(kind_enum = ConfigErrorKind)]
+/// enum ConfigError {
+///     #[error_message("Couldn't read the config")]
+///     Io(#[from] std::io::Error),
+///     #[error_message(&format!("Invalid key {key}"))]
+///     InvalidKey { key: String },
+/// }
+///
+/// let err = ConfigError::InvalidKey { key: "foo".into() };
+/// assert_eq!(err.kind(), ConfigErrorKind::InvalidKey);
+/// ```
+///
+/// ### `constructors`
+///
+/// `#[error_stack(constructors)]` on an enum generates a `#[track_caller]`
+/// constructor per variant, named after the variant in snake case and
+/// taking its fields in order, so `error_stack` can capture where the
+/// error was created. Variants whose constructors would be named like
+/// another generated method, like `IoError` and `IOError`, are rejected
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(constructors)]
+/// enum ConfigError {
+///     #[error_message("The config wasn't found")]
+///     NotFound,
+///     #[error_message(&format!("Invalid key {key}"))]
+///     InvalidKey { key: String },
+/// }
+///
+/// assert!(matches!(ConfigError::not_found(), ConfigError::NotFound));
+/// assert_eq!(
+///     ConfigError::invalid_key("foo".into()).to_string(),
+///     "Invalid key foo"
+/// );
+/// ```
+///
+/// ### `doc_messages`
+///
+/// `#[error_stack(doc_messages)]` on an enum makes the `///` doc comment
+/// of every variant without an `#[error_message]` its message, written as
+/// is. Variants without docs still fall back to the default message
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(doc_messages)]
+/// enum ConfigError {
+///     /// The config file wasn't found
+///     NotFound,
+///     /// The config file isn't valid TOML
+///     #[error_message(&format!("Invalid config at line {line}"))]
+///     Invalid { line: usize },
+///     Unknown,
+/// }
+///
+/// assert_eq!(
+///     ConfigError::NotFound.to_string(),
+///     "The config file wasn't found"
+/// );
+/// assert_eq!(
+///     ConfigError::Invalid { line: 3 }.to_string(),
+///     "Invalid config at line 3"
+/// );
+/// assert_eq!(
+///     ConfigError::Unknown.to_string(),
+///     "[ConfigError] An error occured; Unknown"
+/// );
+/// ```
+///
+/// ### `predicates`
+///
+/// `#[error_stack(predicates)]` on an enum generates an `is_<variant>`
+/// method per variant, named after the variant in snake case, to check
+/// the kind of an error without `matches!`
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(predicates)]
+/// enum ConfigError {
+///     #[error_message("The config wasn't found")]
+///     NotFound,
+///     #[error_message(&format!("Invalid key {key}"))]
+///     InvalidKey { key: String },
+/// }
+///
+/// assert!(ConfigError::NotFound.is_not_found());
+/// assert!(!ConfigError::NotFound.is_invalid_key());
+/// ```
+///
+/// ### `module_prefix`
+///
+/// `#[error_stack(module_prefix)]` prefixes every message with the
+/// [`module_path`] of the deriving item
+///
+/// ```
+/// mod db {
+///     use error_stack_derive::ErrorStack;
+///
+///     #[derive(ErrorStack, Debug)]
+///     #[error_message("connection refused")]
+///     #[error_stack(module_prefix)]
+///     pub struct ConnectionError;
+/// }
+///
+/// assert!(db::ConnectionError
+///     .to_string()
+///     .ends_with("::db: connection refused"));
+/// ```
+///
+/// ### `base`
+///
+/// `#[error_stack(base = "Database error")]` prefixes every message, the
+/// default one included, with the given string, for families of errors
+/// sharing a common prefix
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(base = "Database error")]
+/// enum QueryError {
+///     #[error_message("connection refused")]
+///     Connection,
+///     Timeout,
+/// }
+///
+/// assert_eq!(
+///     QueryError::Connection.to_string(),
+///     "Database error: connection refused"
+/// );
+/// assert_eq!(
+///     QueryError::Timeout.to_string(),
+///     "Database error: [QueryError] An error occured; Timeout"
+/// );
+/// ```
+///
+/// ### `write_fmt`
+///
+/// `#[error_stack(write_fmt)]` treats every message as the arguments of
+/// [`format_args`] and writes it with [`std::fmt::Formatter::write_fmt`],
+/// so the message is never allocated into an intermediate `String`
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(write_fmt)]
+/// enum ParseError {
+///     #[error_message("Unexpected token {unnamed0:?}")]
+///     UnexpectedToken(char),
+///     #[error_message("Expected {} more bytes", needed)]
+///     Incomplete { needed: usize },
+/// }
+///
+/// assert_eq!(
+///     ParseError::UnexpectedToken('}').to_string(),
+///     "Unexpected token '}'"
+/// );
+/// ```
+///
+/// The message has to start with a format string literal
+///
+/// ```compile_fail
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message(&format!("{}", self.0))]
+/// #[error_stack(write_fmt)]
+/// struct FooError(u8);
+/// ```
+///
+/// ### `fmt_with`
+///
+/// `#[error_stack(fmt_with = render)]` implements [`std::fmt::Display`]
+/// by calling `render(self, fmt)`, where `render` is a path to a function
+/// or method with the signature
+/// `fn render(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result`.
+/// It gives full control over the formatting, so `#[error_message]` can't
+/// be used along with it
+///
+/// ```
+/// use std::fmt::{Display, Formatter, Result};
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(fmt_with = Self::render)]
+/// struct PathError(std::path::PathBuf);
+///
+/// impl PathError {
+///     fn render(&self, fmt: &mut Formatter<'_>) -> Result {
+///         fmt.write_str("Invalid path ")?;
+///         self.0.display().fmt(fmt)
+///     }
+/// }
+///
+/// assert_eq!(PathError("/tmp".into()).to_string(), "Invalid path /tmp");
+/// ```
+///
+/// ### `no_display_impl`
+///
+/// `#[error_stack(no_display_impl)]` on a struct with a hand-written
+/// [`std::fmt::Display`] impl only implements [`std::error::Error`], no
+/// message is required and none can be given
+///
+/// ```
+/// use std::error::Error;
+///
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(no_display_impl)]
+/// struct ConfigError {
+///     path: String,
+///     #[source]
+///     inner: std::io::Error,
+/// }
+///
+/// impl std::fmt::Display for ConfigError {
+///     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+///         write!(fmt, "Couldn't read {}", self.path)
+///     }
+/// }
+///
+/// let err = ConfigError {
+///     path: "config.toml".into(),
+///     inner: std::io::ErrorKind::NotFound.into(),
+/// };
+/// assert_eq!(err.to_string(), "Couldn't read config.toml");
+/// assert!(err.source().is_some());
+/// ```
+///
+/// ### `align_names`
+///
+/// `#[error_stack(align_names = 12)]` adds the name of the variant, left
+/// aligned to the given width, to the default message of an enum so the
+/// messages of a list of errors line up. It has no effect on variants
+/// with a dedicated message and can't be used along with a default
+/// message on the enum
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(align_names = 8)]
+/// enum IoError {
+///     NotFound,
+///     Denied,
+/// }
+///
+/// assert_eq!(
+///     IoError::Denied.to_string(),
+///     "[IoError] Denied   An error occured; Denied"
+/// );
+/// ```
+///
+/// ### `fallback_display`
+///
+/// `#[error_stack(fallback_display)]` replaces the default message of an
+/// enum, the variants without a message are written as their name
+/// followed by the [`std::fmt::Display`] representation of their fields,
+/// which suits the fields whose `Debug` representation isn't meaningful
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(fallback_display)]
+/// enum ConfigError {
+///     Missing,
+///     InvalidKey(String),
+///     OutOfRange { key: String, max: u8 },
+/// }
+///
+/// assert_eq!(ConfigError::Missing.to_string(), "Missing");
+/// assert_eq!(
+///     ConfigError::InvalidKey("port".into()).to_string(),
+///     "InvalidKey(port)"
+/// );
+/// let err = ConfigError::OutOfRange {
+///     key: "port".into(),
+///     max: 10,
+/// };
+/// assert_eq!(err.to_string(), "OutOfRange { key: port, max: 10 }");
+/// ```
+///
+/// ### `prefix_with_code`
+///
+/// `#[error_stack(prefix_with_code)]` prefixes the message with the error
+/// code, as in `E404: Not found`, the `E` can be replaced with
+/// `#[error_stack(prefix_with_code = "HTTP")]`. It requires an
+/// `#[error_code]` or `discriminant_code`
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Not found")]
+/// #[error_code(404)]
+/// #[error_stack(prefix_with_code)]
+/// struct NotFoundError;
+///
+/// assert_eq!(NotFoundError.to_string(), "E404: Not found");
+/// ```
+///
+/// ### `display_chain`
+///
+/// `#[error_stack(display_chain)]` generates a
+/// `pub fn display_chain(&self) -> String` method joining the messages
+/// of the error and of its chain of [`std::error::Error::source`]s with
+/// `": "`
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(display_chain)]
+/// enum ConfigError {
+///     #[error_message("Couldn't read the config")]
+///     Io(#[from] std::io::Error),
+/// }
+///
+/// let err = ConfigError::from(std::io::Error::new(
+///     std::io::ErrorKind::NotFound,
+///     "config.toml is missing",
+/// ));
+/// assert_eq!(
+///     err.display_chain(),
+///     "Couldn't read the config: config.toml is missing"
+/// );
+/// ```
+///
+/// `#[error_stack(chain_sep = " -> ")]` joins the messages with another
+/// separator and implies `display_chain`
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message("Couldn't read the config")]
+/// #[error_stack(chain_sep = " -> ")]
+/// struct ConfigError(#[source] std::fmt::Error);
+///
+/// assert_eq!(
+///     ConfigError(std::fmt::Error).display_chain(),
+///     "Couldn't read the config -> an error occurred when formatting an argument"
+/// );
+/// ```
+///
+/// ### `display_test`
+///
+/// `#[error_stack(display_test)]` generates a `#[cfg(test)]`
+/// `pub fn __display_snapshot(&self) -> String` method returning the
+/// message, a standard way to assert on the messages in tests
+///
+/// ```no_run
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(display_test)]
+/// enum ParseError {
+///     #[error_message("Unexpected end of input")]
+///     Eof,
+/// }
+///
+/// #[cfg(test)]
+/// mod tests {
+///     use super::ParseError;
+///
+///     #[test]
+///     fn eof_message() {
+///         assert_eq!(ParseError::Eof.__display_snapshot(), "Unexpected end of input");
+///     }
+/// }
+/// ```
+///
+/// ### `json`
+///
+/// `#[error_stack(json)]` makes [`std::fmt::Display`] write the message as
+/// a JSON object for structured logging,
+/// `{"error":"<name>","message":"<message>"}`, where the name is the one of
+/// the struct or of the variant and the message is escaped for JSON
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(json)]
+/// enum RequestError {
+///     #[error_message("Unexpected \"{0}\" header")]
+///     Header(&'static str),
+/// }
+///
+/// assert_eq!(
+///     RequestError::Header("Host").to_string(),
+///     r#"{"error":"Header","message":"Unexpected \"Host\" header"}"#
+/// );
+/// ```
+///
+/// ### `max_len`
+///
+/// `#[error_stack(max_len = 200)]` truncates the messages to their first
+/// 200 characters followed by an ellipsis, `…`, to bound the length of log
+/// lines. Shorter messages are written unchanged
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message(&format!("Couldn't parse {}", self.0))]
+/// #[error_stack(max_len = 20)]
+/// struct ParseError(String);
+///
+/// assert_eq!(
+///     ParseError("a very long input".into()).to_string(),
+///     "Couldn't parse a ver…"
+/// );
+/// assert_eq!(ParseError("input".into()).to_string(), "Couldn't parse input");
+/// ```
+///
+/// ### `escape_control` and `escape_whitespace`
+///
+/// `#[error_stack(escape_control)]` escapes the control characters of the
+/// messages, which may come from untrusted fields, like
+/// [`char::escape_debug`] so they can't mess with terminals. Newlines and
+/// tabs are kept, `#[error_stack(escape_whitespace)]` escapes them too.
+/// The messages are escaped before being truncated to `max_len`, the
+/// escapes count towards it
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message(&format!("Unknown user {}", self.0))]
+/// #[error_stack(escape_control)]
+/// struct UserError(String);
+///
+/// assert_eq!(
+///     UserError("\x1b[31mroot\x07".into()).to_string(),
+///     "Unknown user \\u{1b}[31mroot\\u{7}"
+/// );
+/// ```
+///
+/// ### `numbered`
+///
+/// `#[error_stack(numbered)]` numbers the lines of multiline messages, like
+/// the problems listed by a validation error, when formatted with `{:#}`.
+/// Single line messages and `{}` are written unchanged
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message(&self.0.join("\n"))]
+/// #[error_stack(numbered)]
+/// struct ValidationError(Vec<&'static str>);
+///
+/// let err = ValidationError(vec!["missing name", "negative age"]);
+/// assert_eq!(format!("{err}"), "missing name\nnegative age");
+/// assert_eq!(format!("{err:#}"), "  1. missing name\n  2. negative age");
+/// ```
+///
+/// ### `bind_fields`
+///
+/// The fields of a variant are bound as locals in its message, so they can
+/// be captured by `format!("{line}")`, while struct messages reach them
+/// through `self`. `#[error_stack(bind_fields)]` binds the fields of a
+/// struct the same way, by their name or as `unnamed{pos}` for tuple
+/// structs
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message(&format!("Invalid key {key} at line {line}"))]
+/// #[error_stack(bind_fields)]
+/// struct InvalidKeyError {
+///     key: String,
+///     line: usize,
+/// }
+///
+/// let err = InvalidKeyError {
+///     key: "port".into(),
+///     line: 3,
+/// };
+/// assert_eq!(err.to_string(), "Invalid key port at line 3");
+/// ```
+///
+/// ### `from_string`
+///
+/// `#[error_stack(from_string)]` implements `From<String>` and
+/// `From<&str>` for a struct with a single `String` field
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_message(&self.0)]
+/// #[error_stack(from_string)]
+/// struct InternalError(String);
+///
+/// let err: InternalError = "Out of memory".into();
+/// assert_eq!(err.to_string(), "Out of memory");
+/// ```
+///
+/// ### `ensure_period` and `ensure_no_period`
+///
+/// `#[error_stack(ensure_period)]` makes every string literal message end
+/// with a period while `#[error_stack(ensure_no_period)]` removes the
+/// trailing ones. Messages ending with a `?`, a `!` or an ellipsis are
+/// left unchanged. The literals are rewritten at compile time, so other
+/// message expressions are left as they are, and with `write_fmt` the
+/// format string is rewritten
+///
+/// ```
+/// use error_stack_derive::ErrorStack;
+///
+/// #[derive(ErrorStack, Debug)]
+/// #[error_stack(ensure_no_period)]
+/// enum LoginError {
+///     #[error_message("Wrong password.")]
+///     WrongPassword,
+///     #[error_message("Unknown user")]
+///     UnknownUser,
+/// }
+///
+/// assert_eq!(LoginError::WrongPassword.to_string(), "Wrong password");
+/// assert_eq!(LoginError::UnknownUser.to_string(), "Unknown user");
+/// ```
+#[proc_macro_derive(
+    ErrorStack,
+    attributes(error_message, error_stack, error_code, source, from, related, provide)
+)]
+pub fn error(tokens: TokenStream) -> TokenStream {
+    derive(tokens.into()).into()
+}
+
+/// The expansion of `#[derive(ErrorStack)]` on `tokens`, independent of
+/// the compiler so it can be tested
+fn derive(tokens: TokenStream2) -> TokenStream2 {
+    let DeriveInput {
+        attrs,
+        vis,
+        ident,
+        generics,
+        data,
+    } = match syn::parse2(tokens) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let ast = Options::from_attrs(&attrs).and_then(|options| match data {
+        Data::Enum(data) => create_enum(attrs, options, vis, ident, generics, data),
+        Data::Struct(data) => create_struct(attrs, options, ident, generics, data),
+        Data::Union(data) => Err(Error::new_spanned(
+            data.union_token,
+            "#[derive(ErrorStack)] only supports structs and enums",
+        )),
+    });
+
+    ast.unwrap_or_else(|err| err.to_compile_error())
+}
+
+/// Declares several unit error structs at once, each one being the
+/// equivalent of a `#[derive(ErrorStack, Debug)]` struct with the given
+/// `#[error_message(...)]`
+///
+/// ```
+/// use error_stack_derive::errors;
+///
+/// errors! {
+///     /// Reading the config failed
+///     pub ConfigError => "Unable to read the config",
+///     #[error_stack(ensure_period)]
+///     ParseError => "Unable to parse the config",
+/// }
+///
+/// assert_eq!(ConfigError.to_string(), "Unable to read the config");
+/// assert_eq!(ParseError.to_string(), "Unable to parse the config.");
+/// ```
+#[proc_macro]
+pub fn errors(tokens: TokenStream) -> TokenStream {
+    expand_errors(tokens.into()).into()
+}
+
+/// The expansion of `errors! { ... }` on `tokens`
+fn expand_errors(tokens: TokenStream2) -> TokenStream2 {
+    syn::parse2::<errors::Errors>(tokens)
+        .and_then(errors::Errors::expand)
+        .unwrap_or_else(|err| err.to_compile_error())
+}
+
+fn create_enum(
+    attrs: Vec<Attribute>,
+    options: Options,
+    vis: Visibility,
+    ident: Ident,
+    generics: Generics,
+    DataEnum {
+        enum_token: _,
+        brace_token: _,
+        variants,
+    }: DataEnum,
+) -> Result<TokenStream2> {
+    if let Some(key) = &options.from_string {
+        return Err(Error::new_spanned(
+            key,
+            "from_string only applies to structs",
+        ));
+    }
+    if let Some(key) = &options.no_display_impl {
+        return Err(Error::new_spanned(
+            key,
+            "no_display_impl only applies to structs",
+        ));
+    }
+    if let Some(key) = &options.bind_fields {
+        return Err(Error::new_spanned(
+            key,
+            "bind_fields only applies to structs, the fields of variants are always bound",
+        ));
+    }
+    if let Some(key) = &options.fallback_display {
+        if message_attr(&attrs).is_some() || options.align_names.is_some() {
+            return Err(Error::new_spanned(
+                key,
+                "fallback_display replaces the default message, \
+                 it can't be used along with a default message or align_names",
+            ));
+        }
+    }
+    if let (Some(width), Some(_)) = (&options.align_names, message_attr(&attrs)) {
+        return Err(Error::new_spanned(
+            width,
+            "align_names only applies to the default message of enums, \
+             it can't be used along with an #[error_message] on the enum",
+        ));
+    }
+    let name = type_name(&options, &ident);
+    let message = match message_attr(&attrs) {
+        Some(attr) => Message::expr_from_attr(attr, &options, None)?,
+        None => Message::Tokens(quote!(::std::format!(
+            "[{name}] An error occured; {:?}",
+            self,
+            name = #name,
+        ))),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let fmt = formatter_ident();
+    let write_default = message.write(&fmt);
+    let prefix = display_prefix(&options, &fmt);
+
+    let mut source_arms = quote!();
+    let mut from_impls = quote!();
+    for variant in &variants {
+        let variant_ident = &variant.ident;
+        let cfgs = cfgs(&variant.attrs);
+        let source = match (Source::from_fields(&variant.fields)?, &options.source_index) {
+            (None, Some(index)) => Source::from_index(&variant.fields, index)?,
+            (source, _) => source,
+        };
+
+        // A transparent variant forwards to the source of its field, which
+        // may still be marked with `#[from]`
+        match (
+            transparent_member(&variant.attrs, &variant.fields)?,
+            &source,
+        ) {
+            (Some(member), _) => source_arms.append_all(quote! {
+                #cfgs
+                Self::#variant_ident { #member: inner, .. } => ::std::error::Error::source(inner),
+            }),
+            (None, Some(Source { member, .. })) => source_arms.append_all(quote! {
+                #cfgs
+                Self::#variant_ident { #member: source, .. } => {
+                    ::std::option::Option::Some(source as &(dyn ::std::error::Error + 'static))
+                }
+            }),
+            (None, None) => {}
+        }
+
+        if let Some(FromField { member, ty, map }) = source.and_then(|source| source.from) {
+            let build = match map {
+                Some(map) => quote!((#map)(source)),
+                None => quote!(Self::#variant_ident { #member: source }),
+            };
+            from_impls.append_all(quote! {
+                #cfgs
+                impl #impl_generics ::std::convert::From<#ty> for #ident #ty_generics #where_clause {
+                    fn from(source: #ty) -> Self {
+                        #build
+                    }
+                }
+            });
+        }
+    }
+    let source = match source_arms.is_empty() {
+        true => None,
+        false => Some(quote! {
+            match self {
+                #source_arms
+                _ => ::std::option::Option::None,
+            }
+        }),
+    };
+    let mut provide_arms = quote!();
+    for variant in &variants {
+        let provided = provided_fields(&variant.fields)?;
+        if provided.is_empty() {
+            continue;
+        }
+        let variant_ident = &variant.ident;
+        let cfgs = cfgs(&variant.attrs);
+        let bindings = (0..provided.len()).map(|pos| format_ident!("provided{}", pos));
+        let members = provided.iter().map(|(member, _)| member);
+        let provides = provided
+            .iter()
+            .zip(bindings.clone())
+            .map(|((_, ty), binding)| quote!(request.provide_ref::<#ty>(#binding);));
+        provide_arms.append_all(quote! {
+            #cfgs
+            Self::#variant_ident { #(#members: #bindings,)* .. } => { #(#provides)* }
+        });
+    }
+    let provides = match provide_arms.is_empty() {
+        true => quote!(),
+        false => quote! {
+            match self {
+                #provide_arms
+                _ => {}
+            }
+        },
+    };
+    let error_body = error_body(&options, source, provides)?;
+
+    let mut related_arms = quote!();
+    for variant in &variants {
+        if let Some(member) = related_field(&variant.fields)? {
+            let variant_ident = &variant.ident;
+            let cfgs = cfgs(&variant.attrs);
+            let iter = related_iter(quote!(related));
+            related_arms.append_all(quote! {
+                #cfgs
+                Self::#variant_ident { #member: related, .. } => ::std::boxed::Box::new(#iter),
+            });
+        }
+    }
+
+    // An error code on the enum is the default of the variants without one
+    let default_code = code::error_code(&attrs)?;
+    let mut code_arms = quote!();
+    let mut missing_code = None;
+    for variant in &variants {
+        let variant_ident = &variant.ident;
+        match code::error_code(&variant.attrs)? {
+            Some(code) => {
+                let cfgs = cfgs(&variant.attrs);
+                code_arms.append_all(quote! {
+                    #cfgs
+                    Self::#variant_ident { .. } => #code,
+                });
+            }
+            None => missing_code = missing_code.or(Some(variant_ident)),
+        }
+    }
+    let has_code = default_code.is_some() || !code_arms.is_empty();
+    match (&default_code, missing_code) {
+        (Some(code), _) => code_arms.append_all(quote!(_ => #code,)),
+        (None, Some(variant_ident)) if has_code => {
+            return Err(Error::new_spanned(
+                variant_ident,
+                "missing #[error_code] on this variant, every variant needs one \
+                 unless the enum declares a default #[error_code]",
+            ))
+        }
+        (None, _) => {}
+    }
+
+    let mut inherent = quote!();
+    if has_code {
+        inherent.append_all(code::code_method(
+            quote!(u32),
+            quote! {
+                #[allow(unreachable_patterns)]
+                match self {
+                    #code_arms
+                }
+            },
+        ));
+        from_impls.append_all(code::code_from_impl(&ident, &generics, quote!(u32)));
+    }
+    if let Some(key) = &options.discriminant_code {
+        if has_code {
+            return Err(Error::new_spanned(
+                key,
+                "discriminant_code can't be used along with #[error_code]",
+            ));
+        }
+        // The discriminants fit in the repr of the enum, if any
+        let ty = match code::repr_type(&attrs)? {
+            Some(repr) => quote!(#repr),
+            None => quote!(i64),
+        };
+        let body = code::discriminant_code(&variants, &ty)?;
+        inherent.append_all(code::code_method(ty.to_owned(), body));
+        from_impls.append_all(code::code_from_impl(&ident, &generics, ty));
+    }
+    code::require_code(&options, has_code || options.discriminant_code.is_some())?;
+    let mut kind_enum = quote!();
+    if let Some(kind) = &options.kind_enum {
+        let mut kind_variants = quote!();
+        let mut kind_arms = quote!();
+        for variant in &variants {
+            let cfgs = cfgs(&variant.attrs);
+            let variant_ident = &variant.ident;
+            let docs = variant
+                .attrs
+                .iter()
+                .filter(|attr| attr.path.is_ident("doc"));
+            kind_variants.append_all(quote!(#(#docs)* #cfgs #variant_ident,));
+            kind_arms
+                .append_all(quote!(#cfgs Self::#variant_ident { .. } => #kind::#variant_ident,));
+        }
+
+        let kind_doc = format!("[`{ident}`]");
+        kind_enum = quote! {
+            /// The variants of
+            #[doc = #kind_doc]
+            /// without their fields
+            #[derive(
+                ::std::fmt::Debug,
+                ::std::clone::Clone,
+                ::std::marker::Copy,
+                ::std::cmp::PartialEq,
+                ::std::cmp::Eq,
+                ::std::hash::Hash,
+            )]
+            #vis enum #kind {
+                #kind_variants
+            }
+        };
+        inherent.append_all(quote! {
+            /// The kind of this error, its variant without the fields
+            pub fn kind(&self) -> #kind {
+                match *self {
+                    #kind_arms
+                }
+            }
+        });
+    }
+    // The other methods of the inherent impl, which the constructors and
+    // predicates can't be named like
+    let mut methods = [
+        ("code", has_code || options.discriminant_code.is_some()),
+        ("kind", options.kind_enum.is_some()),
+        ("related", !related_arms.is_empty()),
+        (
+            "display_chain",
+            options.display_chain || options.chain_sep.is_some(),
+        ),
+        ("__display_snapshot", options.display_test),
+    ]
+    .into_iter()
+    .filter(|(_, generated)| *generated)
+    .map(|(method, _)| method.to_owned())
+    .collect();
+    if options.constructors.is_some() {
+        inherent.append_all(constructors::constructors(&variants, &mut methods)?);
+    }
+    if options.predicates.is_some() {
+        inherent.append_all(constructors::predicates(&variants, &mut methods)?);
+    }
+    if !related_arms.is_empty() {
+        inherent.append_all(related_method(quote! {
+            let related: ::std::boxed::Box<
+                dyn ::std::iter::Iterator<Item = &(dyn ::std::error::Error + 'static)> + '_,
+            > = match self {
+                #related_arms
+                _ => ::std::boxed::Box::new(::std::iter::empty()),
+            };
+            related
+        }));
+    }
+    let inherent = inherent_impl(&options, &ident, &generics, inherent);
+    let mut assertion = send_sync_assertion(&options, &ident, &generics);
+
+    // The arms follow the declaration order of the variants, the catch-all
+    // arm of the variants without a message always comes last
+    let mut match_arms = quote!();
+    let mut covered = 0;
+    for variant in &variants {
+        reject_field_messages(&variant.fields)?;
+
+        let ident = variant.ident.to_owned();
+        let doc = doc_message(&options, &variant.attrs);
+        let message = match (message_attr(&variant.attrs), doc) {
+            (Some(attr), _) => Message::from_attr(attr, &options, Scope::Variant(&variant.fields))?,
+            // Written as is, the braces of docs aren't placeholders
+            (None, Some(mut doc)) => {
+                if let Some(period) = &options.period {
+                    doc = period.apply(&doc);
+                }
+                Message::Tokens(quote!(#doc))
+            }
+            (None, None) => {
+                // Aligning needs the variant's name, so every variant falling
+                // back to the default message gets a dedicated arm
+                if let Some(width) = &options.align_names {
+                    let cfgs = cfgs(&variant.attrs);
+                    let variant_name = LitStr::new(&ident.unraw().to_string(), ident.span());
+                    match_arms.append_all(quote! {
+                        #cfgs
+                        Self::#ident { .. } => ::std::write!(
+                            #fmt,
+                            "[{}] {:<width$} An error occured; {:?}",
+                            #name,
+                            #variant_name,
+                            self,
+                            width = #width,
+                        ),
+                    });
+                    covered += 1;
+                } else if options.fallback_display.is_some() {
+                    match_arms.append_all(fallback_display_arm(variant, &fmt));
+                    covered += 1;
+                }
+                continue;
+            }
+        };
+
+        let cfgs = cfgs(&variant.attrs);
+        let write = message.write(&fmt);
+        covered += 1;
+
+        let pattern = match &message {
+            Message::Tokens(tokens) | Message::Display(tokens) | Message::Pad(tokens) => {
+                bindings::pattern(&variant.fields, tokens, false)
+            }
+            Message::Format(args) => bindings::pattern(&variant.fields, args, true),
+            // Nothing is referenced but `self`, so don't bind the fields
+            Message::Debug(_) => quote!({ .. }),
+        };
+        match_arms.append_all(quote! {
+            #cfgs
+            Self::#ident #pattern => #write,
+        });
+    }
+
+    // The default message writes the `Debug` representation, which would
+    // recurse into the message with `rich_debug`. `fmt_with` writes no
+    // message at all
+    if let (true, None, None, None) = (
+        options.rich_debug,
+        message_attr(&attrs),
+        &options.fallback_display,
+        &options.fmt_with,
+    ) {
+        if let Some(variant) = variants
+            .iter()
+            .find(|variant| uses_default_message(&options, variant))
+        {
+            return Err(Error::new_spanned(
+                &variant.ident,
+                "rich_debug requires a message on this variant or on the enum, \
+                 the default message writes the Debug representation",
+            ));
+        }
+    }
+
+    // The default message writes the `Debug` representation, asserting it's
+    // implemented reports a missing impl at the first variant relying on it
+    if let (None, None) = (message_attr(&attrs), &options.fallback_display) {
+        if let Some(variant) = variants
+            .iter()
+            .find(|variant| uses_default_message(&options, variant))
+        {
+            assertion.append_all(debug_assertion(&ident, &generics, &variant.ident));
+        }
+    }
+
+    // The catch-all arm would be unreachable when every variant has a
+    // dedicated message, an empty enum still needs it to match on `&self`
+    let non_exhaustive = attrs
+        .iter()
+        .any(|attr| attr.path.is_ident("non_exhaustive"));
+    if variants.is_empty() || covered < variants.len() || non_exhaustive {
+        match_arms.append_all(quote!(_ => #write_default,));
+    }
+
+    let write = match &options.fmt_with {
+        Some(fmt_with) => {
+            reject_messages(attrs.iter().chain(variants.iter().flat_map(|v| &v.attrs)))?;
+            quote!(#fmt_with(self, #fmt))
+        }
+        None => quote! {
+            match self {
+                #match_arms
+            }
+        },
+    };
+    let mut display = quote! {
+        #prefix
+        #write
+    };
+    // Escaped before being truncated, so the escapes count towards `max_len`
+    if options.escape_control || options.escape_whitespace {
+        display = escaped_display(&fmt, options.escape_whitespace, display);
+    }
+    if let Some(max_len) = &options.max_len {
+        display = truncated_display(&fmt, max_len, display);
+    }
+    if options.numbered {
+        display = numbered_display(&fmt, display);
+    }
+    // The name of the variant of `self`, an empty enum has no variant
+    let mut name_arms = quote!();
+    for variant in &variants {
+        let cfgs = cfgs(&variant.attrs);
+        let variant_ident = &variant.ident;
+        let variant_name = LitStr::new(&variant_ident.unraw().to_string(), variant_ident.span());
+        name_arms.append_all(quote! {
+            #cfgs
+            Self::#variant_ident { .. } => #variant_name,
+        });
+    }
+    if variants.is_empty() {
+        name_arms.append_all(quote!(_ => #name,));
+    }
+    let variant_name = quote!(match self { #name_arms });
+
+    if options.json {
+        display = json_display(&fmt, variant_name.to_owned(), display);
+    }
+    let error_cfg = error_cfg(&options);
+    let debug = match options.rich_debug {
+        true => rich_debug_impl(&ident, &generics, variant_name),
+        false => quote!(),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Display for #ident #ty_generics #where_clause {
+            fn fmt(&self, #fmt: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                #display
+            }
+        }
+
+        #debug
+
+        #error_cfg
+        impl #impl_generics ::std::error::Error for #ident #ty_generics #where_clause {
+            #error_body
+        }
+
+        #from_impls
+
+        #inherent
+
+        #kind_enum
+
+        #assertion
+    })
+}
+
+fn create_struct(
+    attrs: Vec<Attribute>,
+    options: Options,
+    ident: Ident,
+    generics: Generics,
+    DataStruct { fields, .. }: DataStruct,
+) -> Result<TokenStream2> {
+    reject_field_messages(&fields)?;
+    if let Some(width) = &options.align_names {
+        return Err(Error::new_spanned(
+            width,
+            "align_names only applies to the default message of enums",
+        ));
+    }
+
+    if let Some(kind) = &options.kind_enum {
+        return Err(Error::new_spanned(kind, "kind_enum only applies to enums"));
+    }
+    if let Some(key) = &options.discriminant_code {
+        return Err(Error::new_spanned(
+            key,
+            "discriminant_code only applies to enums",
+        ));
+    }
+    if let Some(key) = &options.constructors {
+        return Err(Error::new_spanned(
+            key,
+            "constructors only applies to enums",
+        ));
+    }
+    if let Some(key) = &options.predicates {
+        return Err(Error::new_spanned(key, "predicates only applies to enums"));
+    }
+    if let Some(index) = &options.source_index {
+        return Err(Error::new_spanned(
+            index,
+            "source_index only applies to enums",
+        ));
+    }
+    if let Some(key) = &options.fallback_display {
+        return Err(Error::new_spanned(
+            key,
+            "fallback_display only applies to enums",
+        ));
+    }
+    if let Some(key) = &options.doc_messages {
+        return Err(Error::new_spanned(
+            key,
+            "doc_messages only applies to enums",
+        ));
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let fmt = formatter_ident();
+    let write = match (&options.no_display_impl, &options.fmt_with) {
+        (Some(key), _) => {
+            reject_display_options(key, &options)?;
+            reject_messages(&attrs)?;
+            quote!()
+        }
+        (None, Some(fmt_with)) => {
+            reject_messages(&attrs)?;
+            quote!(#fmt_with(self, #fmt))
+        }
+        (None, None) => {
+            let attr = message_attr(&attrs).ok_or_else(|| missing_message(&ident))?;
+            Message::from_attr(attr, &options, Scope::Struct(&fields))?.write(&fmt)
+        }
+    };
+    let mut prefix = display_prefix(&options, &fmt);
+    if options.bind_fields.is_some() {
+        let pattern = bind_fields(&fields);
+        prefix = quote! {
+            #[allow(unused_variables)]
+            let Self #pattern = self;
+            #prefix
+        };
+    }
+    let source = Source::from_fields(&fields)?;
+    let from_impl = match source.as_ref().and_then(|source| source.from.as_ref()) {
+        Some(FromField { member, ty, map }) => {
+            let build = match map {
+                Some(map) => quote!((#map)(source)),
+                None => quote!(Self { #member: source }),
+            };
+            quote! {
+                impl #impl_generics ::std::convert::From<#ty> for #ident #ty_generics #where_clause {
+                    fn from(source: #ty) -> Self {
+                        #build
+                    }
+                }
+            }
+        }
+        None => quote!(),
+    };
+    // A transparent struct forwards to the source of its field
+    let source = match (transparent_member(&attrs, &fields)?, source) {
+        (Some(member), _) => Some(quote!(::std::error::Error::source(&self.#member))),
+        (None, Some(Source { member, .. })) => Some(quote! {
+            ::std::option::Option::Some(&self.#member as &(dyn ::std::error::Error + 'static))
+        }),
+        (None, None) => None,
+    };
+    let provides = provided_fields(&fields)?
+        .into_iter()
+        .map(|(member, ty)| quote!(request.provide_ref::<#ty>(&self.#member);))
+        .collect();
+    let error_body = error_body(&options, source, provides)?;
+
+    let from_string = match &options.from_string {
+        Some(key) => from_string_impls(key, &ident, &generics, &fields)?,
+        None => quote!(),
+    };
+
+    let mut inherent = quote!();
+    let mut code_from = quote!();
+    let code = code::error_code(&attrs)?;
+    code::require_code(&options, code.is_some())?;
+    if let Some(code) = code {
+        inherent.append_all(code::code_method(quote!(u32), quote!(#code)));
+        code_from = code::code_from_impl(&ident, &generics, quote!(u32));
+    }
+    if let Some(member) = related_field(&fields)? {
+        inherent.append_all(related_method(related_iter(quote!(&self.#member))));
+    }
+    let inherent = inherent_impl(&options, &ident, &generics, inherent);
+    let assertion = send_sync_assertion(&options, &ident, &generics);
+
+    let mut display = quote! {
+        #prefix
+        #write
+    };
+    if options.escape_control || options.escape_whitespace {
+        display = escaped_display(&fmt, options.escape_whitespace, display);
+    }
+    if let Some(max_len) = &options.max_len {
+        display = truncated_display(&fmt, max_len, display);
+    }
+    if options.numbered {
+        display = numbered_display(&fmt, display);
+    }
+    let name = type_name(&options, &ident);
+    if options.json {
+        display = json_display(&fmt, quote!(#name), display);
+    }
+    let error_cfg = error_cfg(&options);
+    let debug = match options.rich_debug {
+        true => rich_debug_impl(&ident, &generics, quote!(#name)),
+        false => quote!(),
+    };
+    let display = match options.no_display_impl {
+        Some(_) => quote!(),
+        None => quote! {
+            impl #impl_generics ::std::fmt::Display for #ident #ty_generics #where_clause {
+                fn fmt(&self, #fmt: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    #display
+                }
+            }
+        },
+    };
+
+    Ok(quote! {
+        #display
+
+        #debug
+
+        #error_cfg
+        impl #impl_generics ::std::error::Error for #ident #ty_generics #where_clause {
+            #error_body
+        }
+
+        #from_impl
+
+        #from_string
+
+        #code_from
+
+        #inherent
+
+        #assertion
+    })
+}
+
+/// The arm of `#[error_stack(fallback_display)]` writing a variant without
+/// a message as its name followed by the [`std::fmt::Display`]
+/// representation of its fields, like `Variant(1, foo)` or
+/// `Variant { line: 1, name: foo }`
+fn fallback_display_arm(variant: &Variant, fmt: &Ident) -> TokenStream2 {
+    let cfgs = cfgs(&variant.attrs);
+    let variant_ident = &variant.ident;
+    let variant_name = variant_ident.unraw().to_string();
+
+    let (pattern, format, bindings) = match &variant.fields {
+        Fields::Named(fields) => {
+            let bindings = fields
+                .named
+                .iter()
+                .filter_map(|field| field.ident.to_owned())
+                .collect::<Vec<_>>();
+            let names = bindings
+                .iter()
+                .map(|binding| format!("{}: {{}}", binding.unraw()))
+                .collect::<Vec<_>>();
+            (
+                quote!({ #(#bindings),* }),
+                format!("{variant_name} {{{{ {} }}}}", names.join(", ")),
+                bindings,
+            )
+        }
+        Fields::Unnamed(fields) => {
+            let bindings = (0..fields.unnamed.len())
+                .map(unnamed_ident)
+                .collect::<Vec<_>>();
+            let placeholders = vec!["{}"; bindings.len()];
+            (
+                quote!((#(#bindings),*)),
+                format!("{variant_name}({})", placeholders.join(", ")),
+                bindings,
+            )
+        }
+        Fields::Unit => (quote!(), variant_name.to_owned(), Vec::new()),
+    };
+
+    let format = LitStr::new(&format, variant_ident.span());
+    quote! {
+        #cfgs
+        Self::#variant_ident #pattern => ::std::write!(#fmt, #format #(, #bindings)*),
+    }
+}
+
+/// The pattern binding the fields of a struct like those of a variant, by
+/// their name or as `unnamed{pos}`
+fn bind_fields(fields: &Fields) -> TokenStream2 {
+    match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!({ #(#names),* })
+        }
+        Fields::Unnamed(fields) => {
+            let names = (0..fields.unnamed.len()).map(unnamed_ident);
+            quote!((#(#names),*))
+        }
+        Fields::Unit => quote!(),
+    }
+}
+
+/// The options writing the message, which can't be used along with
+/// `#[error_stack(no_display_impl)]`
+fn reject_display_options(key: &Ident, options: &Options) -> Result<()> {
+    let conflicts = [
+        ("fmt_with", options.fmt_with.is_some()),
+        ("module_prefix", options.module_prefix),
+        ("base", options.base.is_some()),
+        ("prefix_with_code", options.prefix_with_code.is_some()),
+        ("write_fmt", options.write_fmt),
+        ("json", options.json),
+        ("max_len", options.max_len.is_some()),
+        ("numbered", options.numbered),
+        ("escape_control", options.escape_control),
+        ("escape_whitespace", options.escape_whitespace),
+        ("ensure_period", options.period.is_some()),
+    ];
+    match conflicts.iter().find(|(_, set)| *set) {
+        Some((option, _)) => Err(Error::new(
+            key.span(),
+            format!("no_display_impl can't be used along with {option}"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The `From<String>` and `From<&str>` impls of `#[error_stack(from_string)]`
+fn from_string_impls(
+    key: &Ident,
+    ident: &Ident,
+    generics: &Generics,
+    fields: &Fields,
+) -> Result<TokenStream2> {
+    let field = match fields.iter().collect::<Vec<_>>()[..] {
+        [field] if is_string(&field.ty) => field,
+        _ => {
+            return Err(Error::new_spanned(
+                key,
+                "from_string requires the struct to have a single `String` field",
+            ))
+        }
+    };
+    let member = member(0, field);
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::std::convert::From<::std::string::String> for #ident #ty_generics #where_clause {
+            fn from(message: ::std::string::String) -> Self {
+                Self { #member: message }
+            }
+        }
+
+        impl #impl_generics ::std::convert::From<&str> for #ident #ty_generics #where_clause {
+            fn from(message: &str) -> Self {
+                Self { #member: ::std::borrow::ToOwned::to_owned(message) }
+            }
+        }
+    })
+}
+
+/// Whether `ty` names [`String`], type aliases can't be seen through
+fn is_string(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "String"),
+        _ => false,
+    }
+}
+
+/// The `#[error_message]` attribute out of `attrs`
+fn message_attr(attrs: &[Attribute]) -> Option<&Attribute> {
+    attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("error_message"))
+}
+
+/// The `///` doc comment of a variant as a message, its lines trimmed and
+/// joined with spaces, under `#[error_stack(doc_messages)]`
+fn doc_message(options: &Options, attrs: &[Attribute]) -> Option<LitStr> {
+    options.doc_messages.as_ref()?;
+
+    let mut lines = Vec::new();
+    let mut span = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("doc")) {
+        if let Ok(Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(doc), ..
+        })) = attr.parse_meta()
+        {
+            span.get_or_insert(doc.span());
+            let line = doc.value();
+            if !line.trim().is_empty() {
+                lines.push(line.trim().to_owned());
+            }
+        }
+    }
+
+    match lines.is_empty() {
+        true => None,
+        false => Some(LitStr::new(&lines.join(" "), span?)),
+    }
+}
+
+/// Whether a variant falls back to the default message of its enum
+fn uses_default_message(options: &Options, variant: &Variant) -> bool {
+    message_attr(&variant.attrs).is_none() && doc_message(options, &variant.attrs).is_none()
+}
+
+/// The only field of a struct or variant whose message is
+/// `#[error_message(transparent)]`
+fn transparent_member(attrs: &[Attribute], fields: &Fields) -> Result<Option<Member>> {
+    match message_attr(attrs) {
+        Some(attr) if is_transparent(attr) => single_member(attr, fields).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn is_transparent(attr: &Attribute) -> bool {
+    attr.parse_args::<Ident>()
+        .is_ok_and(|ident| ident == "transparent")
+}
+
+/// The member of the only field of `fields`, which `attr` requires
+fn single_member(attr: &Attribute, fields: &Fields) -> Result<Member> {
+    match fields.iter().next() {
+        Some(field) if fields.len() == 1 => Ok(source::member(0, field)),
+        _ => Err(Error::new_spanned(
+            attr,
+            "#[error_message(transparent)] requires exactly one field",
+        )),
+    }
+}
+
+/// Unlike enums, structs have no default message to fall back to
+fn missing_message(ident: &Ident) -> Error {
+    Error::new(
+        ident.span(),
+        format!(
+            "missing #[error_message(...)] on struct `{ident}`, structs have no default message\n\
+             help: add a message, like #[error_message(\"An error occured in {ident}\")]\n\
+             help: or derive Debug and fall back to its representation with \
+             #[error_message(debug)]"
+        ),
+    )
+}
+
+/// Messages belong to the struct or the enum and its variants, an
+/// `#[error_message]` on a field would otherwise be silently ignored
+fn reject_field_messages(fields: &Fields) -> Result<()> {
+    match fields
+        .iter()
+        .flat_map(|field| message_attr(&field.attrs))
+        .next()
+    {
+        Some(attr) => Err(Error::new_spanned(
+            attr,
+            "#[error_message] on a field is not supported; place it on the struct/variant",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// `fmt_with` replaces every message, so none can be given along with it
+fn reject_messages<'a>(attrs: impl IntoIterator<Item = &'a Attribute>) -> Result<()> {
+    match attrs
+        .into_iter()
+        .find(|attr| attr.path.is_ident("error_message"))
+    {
+        Some(attr) => Err(Error::new_spanned(
+            attr,
+            "#[error_message] can't be used along with #[error_stack(fmt_with = ...)]",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The items of the generated [`std::error::Error`] impl, `source` is the
+/// body of [`std::error::Error::source`] derived from the source fields and
+/// `provides` the statements providing the `#[provide]` fields, every
+/// method goes into this single impl
+fn error_body(
+    options: &Options,
+    source: Option<TokenStream2>,
+    provides: TokenStream2,
+) -> Result<TokenStream2> {
+    let has_source = source.is_some() || options.source_fn.is_some();
+    let source = match (&options.source_fn, source) {
+        (Some(source_fn), None) => Some(quote!(self.#source_fn())),
+        (Some(source_fn), Some(_)) => {
+            return Err(Error::new_spanned(
+                source_fn,
+                "source_fn can't be used along with #[source] or #[from] fields",
+            ))
+        }
+        (None, None) if options.explicit_source => Some(quote!(::std::option::Option::None)),
+        (None, source) => source,
+    };
+
+    let mut body = quote!();
+
+    // Surfaces the provided fields, then what the errors deeper in the
+    // chain provide, like their backtraces
+    if cfg!(feature = "provide") && (source.is_some() || !provides.is_empty()) {
+        let forward = source.as_ref().map(|_| {
+            quote! {
+                if let ::std::option::Option::Some(source) = ::std::error::Error::source(self) {
+                    ::std::error::Error::provide(source, request);
+                }
+            }
+        });
+        body.append_all(quote! {
+            fn provide<'a>(&'a self, request: &mut ::std::error::Request<'a>) {
+                #provides
+                #forward
+            }
+        });
+    }
+
+    if let Some(source) = source {
+        body.append_all(quote! {
+            fn source(&self) -> ::std::option::Option<&(dyn ::std::error::Error + 'static)> {
+                #source
+            }
+        });
+    }
+
+    if options.legacy_cause && has_source {
+        body.append_all(quote! {
+            #[allow(deprecated)]
+            fn cause(&self) -> ::std::option::Option<&dyn ::std::error::Error> {
+                ::std::error::Error::source(self)
+            }
+        });
+    }
+
+    Ok(body)
+}
+
+/// The statements writing what comes before every message to `fmt`
+fn display_prefix(options: &Options, fmt: &Ident) -> TokenStream2 {
+    let mut prefix = quote!();
+
+    if options.module_prefix {
+        prefix.append_all(quote!(::std::write!(#fmt, "{}: ", ::std::module_path!())?;));
+    }
+    if let Some(base) = &options.base {
+        prefix.append_all(quote!(::std::write!(#fmt, "{}: ", #base)?;));
+    }
+    if let Some(code_prefix) = &options.prefix_with_code {
+        prefix.append_all(quote!(::std::write!(#fmt, "{}{}: ", #code_prefix, self.code())?;));
+    }
+
+    prefix
+}
+
+/// A [`std::fmt::Debug`] impl writing `name` and the message, like
+/// `ParseError { display: "Unexpected end of input" }`
+fn rich_debug_impl(ident: &Ident, generics: &Generics, name: TokenStream2) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let fmt = formatter_ident();
+    quote! {
+        impl #impl_generics ::std::fmt::Debug for #ident #ty_generics #where_clause {
+            fn fmt(&self, #fmt: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                #fmt.debug_struct(#name)
+                    .field("display", &::std::string::ToString::to_string(self))
+                    .finish()
+            }
+        }
+    }
+}
+
+/// The `#[cfg(...)]` gating the [`std::error::Error`] impl, if any
+fn error_cfg(options: &Options) -> TokenStream2 {
+    match &options.error_cfg {
+        Some(cfg) => quote!(#[cfg(#cfg)]),
+        None => quote!(),
+    }
+}
+
+/// The name of the deriving item in the messages, overridden by `rename`
+fn type_name(options: &Options, ident: &Ident) -> LitStr {
+    match &options.rename {
+        Some(rename) => rename.to_owned(),
+        None => LitStr::new(&ident.unraw().to_string(), ident.span()),
+    }
+}
+
+/// The body of `Display::fmt` writing `{"error":"<name>","message":"<msg>"}`,
+/// where the message is what `display` writes, both escaped for JSON
+fn json_display(fmt: &Ident, name: TokenStream2, display: TokenStream2) -> TokenStream2 {
+    let render = render_message(fmt, display);
+    quote! {
+        #render
+
+        // A `rename` may need escaping just like the message
+        let escape = |#fmt: &mut ::std::fmt::Formatter<'_>, value: &str| -> ::std::fmt::Result {
+            for c in value.chars() {
+                match c {
+                    '"' => #fmt.write_str("\\\"")?,
+                    '\\' => #fmt.write_str("\\\\")?,
+                    '\n' => #fmt.write_str("\\n")?,
+                    '\r' => #fmt.write_str("\\r")?,
+                    '\t' => #fmt.write_str("\\t")?,
+                    c if (c as u32) < 0x20 => ::std::write!(#fmt, "\\u{:04x}", c as u32)?,
+                    c => ::std::fmt::Write::write_char(#fmt, c)?,
+                }
+            }
+            ::std::result::Result::Ok(())
+        };
+
+        #fmt.write_str("{\"error\":\"")?;
+        escape(#fmt, #name)?;
+        #fmt.write_str("\",\"message\":\"")?;
+        escape(#fmt, &message)?;
+        #fmt.write_str("\"}")
+    }
+}
+
+/// A compile time assertion that the deriving item is
+/// `Send + Sync + 'static`, as required by `error_stack` contexts, under
+/// its own bounds
+fn send_sync_assertion(options: &Options, ident: &Ident, generics: &Generics) -> TokenStream2 {
+    if !options.assert_send_sync {
+        return quote!();
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    // Spanned to the item so the unsatisfied bound is reported there
+    let assert = quote_spanned!(ident.span()=> assert_send_sync::<#ident #ty_generics>());
+    quote! {
+        const _: () = {
+            fn assert_send_sync<T: ?::std::marker::Sized + ::std::marker::Send + ::std::marker::Sync + 'static>() {}
+
+            #[allow(dead_code)]
+            fn assert #impl_generics () #where_clause {
+                #assert;
+            }
+        };
+    }
+}
+
+/// Asserts at compile time that the deriving enum implements
+/// [`std::fmt::Debug`], as its default message requires
+fn debug_assertion(ident: &Ident, generics: &Generics, variant: &Ident) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    // Spanned to the variant falling back to the default message so the
+    // unsatisfied bound is reported there
+    let mut ty = ident.to_owned();
+    ty.set_span(variant.span());
+    let assert = quote_spanned!(variant.span()=> assert_debug::<#ty #ty_generics>());
+    quote! {
+        const _: () = {
+            fn assert_debug<T: ?::std::marker::Sized + ::std::fmt::Debug>() {}
+
+            #[allow(dead_code)]
+            fn assert #impl_generics () #where_clause {
+                #assert;
+            }
+        };
+    }
+}
+
+/// The body of `Display::fmt` writing at most the first `max_len`
+/// characters of what `display` writes, followed by an ellipsis when it's
+/// truncated
+fn truncated_display(fmt: &Ident, max_len: &LitInt, display: TokenStream2) -> TokenStream2 {
+    let render = render_message(fmt, display);
+    quote! {
+        #render
+
+        // Truncates on a char boundary
+        match ::std::iter::Iterator::nth(
+            &mut ::std::primitive::str::char_indices(&message),
+            #max_len,
+        ) {
+            ::std::option::Option::Some((end, _)) => {
+                #fmt.write_str(&message[..end])?;
+                #fmt.write_str("\u{2026}")
+            }
+            ::std::option::Option::None => #fmt.write_str(&message),
+        }
+    }
+}
+
+/// The body of `Display::fmt` writing what `display` writes with its
+/// control characters escaped, except for newlines and tabs unless
+/// `whitespace` is set
+fn escaped_display(fmt: &Ident, whitespace: bool, display: TokenStream2) -> TokenStream2 {
+    let render = render_message(fmt, display);
+    let kept = match whitespace {
+        true => quote!(false),
+        false => quote!(::std::matches!(c, '\n' | '\t')),
+    };
+    quote! {
+        #render
+
+        for c in message.chars() {
+            if c.is_control() && !#kept {
+                ::std::write!(#fmt, "{}", c.escape_debug())?;
+            } else {
+                ::std::fmt::Write::write_char(#fmt, c)?;
+            }
+        }
+        ::std::result::Result::Ok(())
+    }
+}
+
+/// The body of `Display::fmt` writing every line of what `display` writes
+/// prefixed with its number under `{:#}`, when there are several
+fn numbered_display(fmt: &Ident, display: TokenStream2) -> TokenStream2 {
+    let render = render_message(fmt, display.to_owned());
+    quote! {
+        if !#fmt.alternate() {
+            return { #display };
+        }
+
+        #render
+
+        if !::std::primitive::str::contains(&message, '\n') {
+            return #fmt.write_str(&message);
+        }
+        for (pos, line) in
+            ::std::iter::Iterator::enumerate(::std::primitive::str::split(&message, '\n'))
+        {
+            if pos > 0 {
+                #fmt.write_str("\n")?;
+            }
+            ::std::write!(#fmt, "  {}. {}", pos + 1, line)?;
+        }
+        ::std::result::Result::Ok(())
+    }
+}
+
+/// The statements rendering what `display` writes to a `message` string,
+/// so it can be processed before being written
+fn render_message(fmt: &Ident, display: TokenStream2) -> TokenStream2 {
+    quote! {
+        struct __ErrorStackMessage<F>(F);
+
+        impl<F> ::std::fmt::Display for __ErrorStackMessage<F>
+        where
+            F: ::std::ops::Fn(&mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result,
+        {
+            fn fmt(&self, #fmt: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                (self.0)(#fmt)
+            }
+        }
+
+        let message = ::std::string::ToString::to_string(&__ErrorStackMessage(
+            |#fmt: &mut ::std::fmt::Formatter<'_>| -> ::std::fmt::Result { #display },
+        ));
+    }
+}
+
+/// An inherent impl of the deriving item holding the generated `items`
+/// and the methods opted into through `options`, nothing when there are
+/// none
+fn inherent_impl(
+    options: &Options,
+    ident: &Ident,
+    generics: &Generics,
+    mut items: TokenStream2,
+) -> TokenStream2 {
+    if options.display_chain || options.chain_sep.is_some() {
+        let sep = match &options.chain_sep {
+            Some(sep) => sep.value(),
+            None => ": ".to_owned(),
+        };
+        let doc = format!(
+            " The messages of this error and of its chain of sources,\n joined with `{sep:?}`"
+        );
+        // Walks the sources, so it's gated along with the Error impl
+        let error_cfg = error_cfg(options);
+        items.append_all(quote! {
+            #[doc = #doc]
+            #error_cfg
+            pub fn display_chain(&self) -> ::std::string::String {
+                let mut chain = ::std::string::ToString::to_string(self);
+                let mut source = ::std::error::Error::source(self);
+                while let ::std::option::Option::Some(err) = source {
+                    chain.push_str(#sep);
+                    chain.push_str(&::std::string::ToString::to_string(err));
+                    source = ::std::error::Error::source(err);
+                }
+                chain
+            }
+        });
+    }
+
+    if options.display_test {
+        items.append_all(quote! {
+            /// The message of this error, to snapshot in tests
+            #[cfg(test)]
+            pub fn __display_snapshot(&self) -> ::std::string::String {
+                ::std::string::ToString::to_string(self)
+            }
+        });
+    }
+
+    if items.is_empty() {
+        return items;
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #items
+        }
+    }
+}
+
+/// The `related` method returning `body`, an iterator over the errors of
+/// the `#[related]` field
+fn related_method(body: TokenStream2) -> TokenStream2 {
+    quote! {
+        /// The errors collected along with this one
+        pub fn related(
+            &self,
+        ) -> impl ::std::iter::Iterator<Item = &(dyn ::std::error::Error + 'static)> + '_ {
+            #body
+        }
+    }
+}
+
+/// An iterator over the boxed errors of the `#[related]` field `related`
+fn related_iter(related: TokenStream2) -> TokenStream2 {
+    quote! {
+        ::std::iter::Iterator::map(::std::iter::IntoIterator::into_iter(#related), |related| {
+            &**related as &(dyn ::std::error::Error + 'static)
+        })
+    }
+}
+
+/// The `#[cfg]` attributes out of `attrs`, cfg-stripping happens before
+/// derive macros are expanded but generated items and arms keep the cfgs
+/// of their variant so they can never outlive it
+fn cfgs(attrs: &[Attribute]) -> TokenStream2 {
+    let mut cfgs = quote!();
+    cfgs.append_all(attrs.iter().filter(|attr| attr.path.is_ident("cfg")));
+    cfgs
+}
+
+/// The contents of an `#[error_message]` attribute
+enum Message {
+    /// Any tokens evaluating to an [`AsRef<str>`], passed to
+    /// [`std::fmt::Formatter::write_str`]
+    Tokens(TokenStream2),
+    /// With `#[error_stack(write_fmt)]`, the arguments of a
+    /// [`format_args`] written with [`std::fmt::Formatter::write_fmt`]
+    Format(TokenStream2),
+    /// `#[error_message(debug)]`, writes the [`std::fmt::Debug`]
+    /// representation of `self`, spanned to `debug` so a missing `Debug`
+    /// impl is reported there
+    Debug(Span),
+    /// `#[error_message(display = field)]` or
+    /// `#[error_message(display_expr = ...)]`, forwards to the
+    /// [`std::fmt::Display`] impl of the referenced field or of the
+    /// expression
+    Display(TokenStream2),
+    /// `#[error_message(pad(...))]`, any tokens evaluating to an
+    /// [`AsRef<str>`] passed to [`std::fmt::Formatter::pad`], which honors
+    /// the width and the alignment
+    Pad(TokenStream2),
+}
+
+impl Message {
+    fn from_attr(attr: &Attribute, options: &Options, scope: Scope) -> Result<Self> {
+        if let Ok(ident) = attr.parse_args::<Ident>() {
+            if ident == "debug" {
+                if options.rich_debug {
+                    return Err(Error::new(
+                        ident.span(),
+                        "`debug` can't be used along with rich_debug, \
+                         whose Debug representation writes the message",
+                    ));
+                }
+                return Ok(Self::Debug(ident.span()));
+            }
+            if ident == "transparent" {
+                let member = single_member(attr, scope.fields())?;
+                return Ok(Self::Display(scope.field(&member)?));
+            }
+        }
+
+        Self::expr_from_attr(attr, options, Some(scope))
+    }
+
+    /// The message out of `attr`, without the special forms like `debug`.
+    /// The `{field}` placeholders of string literals are interpolated with
+    /// the fields of `scope`, and rejected without one
+    fn expr_from_attr(attr: &Attribute, options: &Options, scope: Option<Scope>) -> Result<Self> {
+        let empty = match attr.parse_args::<TokenStream2>() {
+            Ok(tokens) => tokens.is_empty(),
+            Err(_) => attr.tokens.is_empty(),
+        };
+        if empty {
+            return Err(Error::new_spanned(
+                attr,
+                "#[error_message] requires a message expression",
+            ));
+        }
+
+        if let Ok(tokens) = attr.parse_args_with(pad_wrapper) {
+            let message = match parse::Parser::parse2(literal_message, tokens.to_owned()) {
+                Ok((message, args)) => Self::from_literal(message, &args, options, scope)?,
+                Err(_) => {
+                    let expr: Expr = syn::parse2(tokens)?;
+                    Self::Tokens(quote!(#expr))
+                }
+            };
+            return Ok(match message {
+                Self::Tokens(tokens) => Self::Pad(tokens),
+                Self::Format(args) => Self::Pad(quote!(::std::format!(#args))),
+                message => message,
+            });
+        }
+
+        if let Some(meta) = MessageMeta::from_attr(attr)? {
+            if let Some(expr) = meta.display_expr {
+                return Ok(Self::Display(quote!(&(#expr))));
+            }
+            return match (meta.message, meta.display, scope) {
+                (Some(message), _, scope) => Self::from_literal(message, &[], options, scope),
+                (None, Some(member), Some(scope)) => Ok(Self::Display(scope.field(&member)?)),
+                (None, Some(member), None) => Err(Error::new_spanned(
+                    member,
+                    "`display` only applies to the message of a struct or a variant",
+                )),
+                (None, None, _) => Err(Error::new_spanned(
+                    attr,
+                    "expected a `message = \"...\"`, a `display = field` \
+                     or a `display_expr = expression`",
+                )),
+            };
+        }
+
+        if !options.write_fmt {
+            // Only string literals can be punctuated and interpolated at
+            // macro time
+            return match attr.parse_args_with(literal_message) {
+                Ok((message, args)) => Self::from_literal(message, &args, options, scope),
+                Err(_) => {
+                    let expr: Expr = attr.parse_args()?;
+                    Ok(Self::Tokens(quote!(#expr)))
+                }
+            };
+        }
+
+        attr.parse_args_with(|input: ParseStream| {
+            if !input.peek(LitStr) {
+                return Err(input.error(
+                    "#[error_stack(write_fmt)] expects the message to be the arguments \
+                     of `format_args!`, starting with a format string literal",
+                ));
+            }
+
+            let mut format: LitStr = input.parse()?;
+            if let Some(period) = &options.period {
+                format = period.apply(&format);
+            }
+            let args: TokenStream2 = input.parse()?;
+
+            Ok(Self::Format(quote!(#format #args)))
+        })
+    }
+
+    /// The message out of a string literal, punctuated and interpolated
+    /// with the fields of `scope` or the named `args`
+    fn from_literal(
+        mut message: LitStr,
+        args: &[(Ident, Expr)],
+        options: &Options,
+        scope: Option<Scope>,
+    ) -> Result<Self> {
+        if let Some(period) = &options.period {
+            message = period.apply(&message);
+        }
+        if options.write_fmt {
+            let args = args.iter().map(|(name, expr)| quote!(#name = #expr));
+            return Ok(Self::Format(quote!(#message #(, #args)*)));
+        }
+
+        let Scanned {
+            format,
+            members,
+            params,
+        } = match placeholders::scan(&message)? {
+            Some(scanned) => scanned,
+            None => match args.first() {
+                Some((name, _)) => {
+                    return Err(Error::new_spanned(
+                        name,
+                        "argument never used, the message has no placeholder",
+                    ))
+                }
+                None => return Ok(Self::Tokens(quote!(#message))),
+            },
+        };
+
+        // A named argument or else a field of the struct or variant
+        let arg = |member: &Member| -> Option<&Expr> {
+            match member {
+                Member::Named(ident) => args
+                    .iter()
+                    .find_map(|(name, expr)| (name == ident).then_some(expr)),
+                Member::Unnamed(_) => None,
+            }
+        };
+        // `usage` formats the name of the field as it's used in the message
+        let field = |member: &Member, usage: fn(&str) -> String| -> Result<TokenStream2> {
+            let name = match member {
+                Member::Named(ident) => ident.unraw().to_string(),
+                Member::Unnamed(index) => index.index.to_string(),
+            };
+            match scope {
+                Some(scope) => scope.field(member).map_err(|_| {
+                    Error::new(
+                        message.span(),
+                        format!(
+                            "no field or argument `{name}` for `{}`, \
+                             escape the braces as `{{{{` and `}}}}` to write them",
+                            usage(&name),
+                        ),
+                    )
+                }),
+                // Would otherwise be written with the braces
+                None => Err(Error::new(
+                    message.span(),
+                    "the default message of an enum has no fields to interpolate, \
+                     use `&format!(\"...\", self)` or escape the braces as `{{` and `}}`",
+                )),
+            }
+        };
+
+        let mut format_args = Vec::new();
+        for member in &members {
+            format_args.push(match arg(member) {
+                Some(expr) => quote!(&(#expr)),
+                None => field(member, |name| format!("{{{name}}}"))?,
+            });
+        }
+        for member in &params {
+            // Width and precision parameters are passed by value
+            format_args.push(match (member, arg(member)) {
+                (Member::Named(name), Some(expr)) => quote!(#name = #expr),
+                (Member::Named(name), None) => {
+                    let field = field(member, |name| format!("{name}$"))?;
+                    quote!(#name = *#field)
+                }
+                (Member::Unnamed(index), _) => {
+                    let name = unnamed_ident(index.index as usize);
+                    let field = field(member, |name| format!("{name}$"))?;
+                    quote!(#name = *#field)
+                }
+            });
+        }
+        if let Some((name, _)) = args.iter().find(|(name, _)| {
+            !members
+                .iter()
+                .chain(&params)
+                .any(|member| member == &Member::Named(name.to_owned()))
+        }) {
+            return Err(Error::new_spanned(
+                name,
+                "argument never used in the message",
+            ));
+        }
+
+        Ok(Self::Format(quote!(#format #(, #format_args)*)))
+    }
+
+    /// The expression writing this message to `fmt`
+    fn write(&self, fmt: &Ident) -> TokenStream2 {
+        match self {
+            // Matching keeps the temporaries of the expression alive, the
+            // parentheses let it be a struct literal
+            Self::Tokens(tokens) => quote! {
+                match (#tokens) {
+                    message => #fmt.write_str(::std::convert::AsRef::<str>::as_ref(&message)),
+                }
+            },
+            Self::Format(args) => quote!(#fmt.write_fmt(::std::format_args!(#args))),
+            Self::Debug(span) => quote_spanned!(*span=> ::std::write!(#fmt, "{:?}", self)),
+            Self::Display(field) => quote!(::std::fmt::Display::fmt(#field, #fmt)),
+            Self::Pad(tokens) => quote! {
+                match (#tokens) {
+                    message => #fmt.pad(::std::convert::AsRef::<str>::as_ref(&message)),
+                }
+            },
+        }
+    }
+}
+
+/// The key-value form of `#[error_message(...)]`, like
+/// `#[error_message(message = "Not found", code = 404)]`
+#[derive(Default)]
+struct MessageMeta {
+    /// `message = "..."`, or its alias `template = "..."`
+    message: Option<LitStr>,
+    /// `display = field`
+    display: Option<Member>,
+    /// `display_expr = self.inner()`, any expression implementing
+    /// [`std::fmt::Display`]
+    display_expr: Option<Expr>,
+    /// `code = 404`, the same as `#[error_code(404)]`
+    code: Option<LitInt>,
+}
+
+impl MessageMeta {
+    /// `None` when `attr` isn't in the key-value form
+    fn from_attr(attr: &Attribute) -> Result<Option<Self>> {
+        let is_meta = attr.parse_args_with(|input: ParseStream| {
+            let is_meta = input.peek(Ident) && input.peek2(Token![=]);
+            input.parse::<TokenStream2>()?;
+            Ok(is_meta)
+        });
+        if !is_meta.unwrap_or(false) {
+            return Ok(None);
+        }
+
+        attr.parse_args_with(|input: ParseStream| {
+            let mut meta = Self::default();
+            while !input.is_empty() {
+                let key: Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
+
+                match key.to_string().as_str() {
+                    "message" | "template" => set_key(&mut meta.message, &key, input.parse()?)?,
+                    "display" => set_key(&mut meta.display, &key, input.parse()?)?,
+                    "display_expr" => set_key(&mut meta.display_expr, &key, input.parse()?)?,
+                    "code" => {
+                        let code: LitInt = input.parse()?;
+                        code.base10_parse::<u32>()?;
+                        set_key(&mut meta.code, &key, code)?
+                    }
+                    _ => {
+                        return Err(Error::new(
+                            key.span(),
+                            format!(
+                                "unknown error_message key `{key}`, expected one of \
+                                 `message`, `template`, `display`, `display_expr` or `code`"
+                            ),
+                        ))
+                    }
+                }
+
+                if input.is_empty() {
+                    break;
+                }
+                input.parse::<Token![,]>()?;
+            }
+
+            let messages = [
+                meta.message.is_some(),
+                meta.display.is_some(),
+                meta.display_expr.is_some(),
+            ];
+            if messages.iter().filter(|&&set| set).count() > 1 {
+                return Err(Error::new_spanned(
+                    attr,
+                    "only one of `message`, `display` and `display_expr` can be used",
+                ));
+            }
+            Ok(Some(meta))
+        })
+    }
+}
+
+fn set_key<T>(value: &mut Option<T>, key: &Ident, new: T) -> Result<()> {
+    match value {
+        Some(_) => Err(Error::new(
+            key.span(),
+            format!("duplicate error_message key `{key}`"),
+        )),
+        None => {
+            *value = Some(new);
+            Ok(())
+        }
+    }
+}
+
+/// The message out of `pad(...)`
+fn pad_wrapper(input: ParseStream) -> Result<TokenStream2> {
+    let ident: Ident = input.parse()?;
+    if ident != "pad" {
+        return Err(Error::new(ident.span(), "expected `pad(...)`"));
+    }
+
+    let content;
+    syn::parenthesized!(content in input);
+    if !input.is_empty() {
+        return Err(input.error("unexpected tokens after `pad(...)`"));
+    }
+    content.parse()
+}
+
+/// The message out of comma separated string literals, concatenated so
+/// long messages can be split across lines, followed by the `name = expr`
+/// arguments its placeholders and parameters may refer to
+fn literal_message(input: ParseStream) -> Result<(LitStr, Vec<(Ident, Expr)>)> {
+    let first: LitStr = input.parse()?;
+    let mut value = first.value();
+    while input.peek(Token![,]) && input.peek2(LitStr) {
+        input.parse::<Token![,]>()?;
+        value.push_str(&input.parse::<LitStr>()?.value());
+    }
+
+    let mut args = Vec::new();
+    while input.peek(Token![,]) {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+        let name: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        args.push((name, input.parse()?));
+    }
+
+    Ok((LitStr::new(&value, first.span()), args))
+}
+
+/// The fields a message is written for, accessed through `self` for
+/// structs and through the bindings of the match arm for enum variants
+#[derive(Clone, Copy)]
+enum Scope<'a> {
+    Struct(&'a Fields),
+    Variant(&'a Fields),
+}
+
+impl<'a> Scope<'a> {
+    fn fields(self) -> &'a Fields {
+        match self {
+            Self::Struct(fields) | Self::Variant(fields) => fields,
+        }
+    }
+
+    /// The expression borrowing the field `member`
+    fn field(self, member: &Member) -> Result<TokenStream2> {
+        let fields = self.fields();
+        if !fields
+            .iter()
+            .enumerate()
+            .any(|(pos, field)| &source::member(pos, field) == member)
+        {
+            return Err(Error::new_spanned(
+                member,
+                "no field with this name or index",
+            ));
+        }
+
+        Ok(match (self, member) {
+            (Self::Struct(_), member) => quote!(&self.#member),
+            (Self::Variant(_), Member::Named(ident)) => quote!(#ident),
+            (Self::Variant(_), Member::Unnamed(index)) => {
+                let ident = unnamed_ident(index.index as usize);
+                quote!(#ident)
+            }
+        })
+    }
+}
+
+/// The binding of the `pos`th field of a tuple variant in the match arms
+fn unnamed_ident(pos: usize) -> Ident {
+    Ident::new(&format!("unnamed{pos}"), Span::call_site())
+}
+
+/// The binding of the [`std::fmt::Formatter`] in the generated `Display`
+/// impls, it has a mixed site span so the message tokens can neither
+/// shadow nor reference it
+fn formatter_ident() -> Ident {
+    Ident::new("fmt", Span::mixed_site())
 }
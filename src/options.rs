@@ -0,0 +1,282 @@
+//! Parsing of the item level `#[error_stack(...)]` attribute
+
+use syn::{parse::ParseStream, Attribute, Error, Ident, LitInt, LitStr, Meta, Path, Result, Token};
+
+/// The options passed to the deriving item through
+/// `#[error_stack(...)]`, every option is optional and the options of
+/// multiple attributes are merged together
+#[derive(Default)]
+pub(crate) struct Options {
+    /// `source_fn = my_source`, an inherent method with the signature
+    /// `fn my_source(&self) -> Option<&(dyn std::error::Error + 'static)>`
+    /// used as [`std::error::Error::source`]
+    pub(crate) source_fn: Option<Ident>,
+    /// `explicit_source`, implements [`std::error::Error::source`]
+    /// returning `None` when there's no source instead of relying on the
+    /// default method
+    pub(crate) explicit_source: bool,
+    /// `source_index = 0`, the index of the field of every tuple variant
+    /// used as the source when none is marked
+    pub(crate) source_index: Option<LitInt>,
+    /// `legacy_cause`, also implements the deprecated
+    /// [`std::error::Error::cause`] returning the source
+    pub(crate) legacy_cause: bool,
+    /// `error_cfg = "feature = \"std\""`, the predicate the
+    /// [`std::error::Error`] impl is gated by
+    pub(crate) error_cfg: Option<Meta>,
+    /// `assert_send_sync`, asserts at compile time that the deriving item
+    /// is `Send + Sync + 'static`
+    pub(crate) assert_send_sync: bool,
+    /// `kind_enum = ErrorKind`, the name of the generated enum holding the
+    /// variants of the deriving enum without their fields
+    pub(crate) kind_enum: Option<Ident>,
+    /// `constructors`, generates a `#[track_caller]` constructor per
+    /// variant
+    pub(crate) constructors: Option<Ident>,
+    /// `predicates`, generates an `is_<variant>` method per variant
+    pub(crate) predicates: Option<Ident>,
+    /// `doc_messages`, the doc comments of variants are their messages
+    pub(crate) doc_messages: Option<Ident>,
+    /// `rename = "Name"`, the name of the deriving item in the messages
+    pub(crate) rename: Option<LitStr>,
+    /// `discriminant_code`, generates a `code` method returning the
+    /// discriminants of a fieldless enum
+    pub(crate) discriminant_code: Option<Ident>,
+    /// `rich_debug`, implements [`std::fmt::Debug`] with the name and the
+    /// message instead of the fields
+    pub(crate) rich_debug: bool,
+    /// `module_prefix`, prefixes every message with the [`module_path`]
+    /// of the deriving item
+    pub(crate) module_prefix: bool,
+    /// `base = "Database error"`, prefixes every message with the given
+    /// string
+    pub(crate) base: Option<LitStr>,
+    /// `prefix_with_code` or `prefix_with_code = "E"`, prefixes every
+    /// message with the code of the error after the given string
+    pub(crate) prefix_with_code: Option<LitStr>,
+    /// `write_fmt`, messages are the arguments of a [`format_args`]
+    /// instead of an expression evaluating to a string
+    pub(crate) write_fmt: bool,
+    /// `fmt_with = render`, a function with the signature
+    /// `fn render(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result`
+    /// called by [`std::fmt::Display::fmt`] instead of writing a message
+    pub(crate) fmt_with: Option<Path>,
+    /// `no_display_impl`, only implements [`std::error::Error`] for
+    /// structs with a hand-written [`std::fmt::Display`] impl
+    pub(crate) no_display_impl: Option<Ident>,
+    /// `align_names = 12`, the width the variant names are left aligned to
+    /// in the default message of enums
+    pub(crate) align_names: Option<LitInt>,
+    /// `fallback_display`, the default message of enums writes the name of
+    /// the variant and the [`std::fmt::Display`] representation of its
+    /// fields instead of the [`std::fmt::Debug`] representation
+    pub(crate) fallback_display: Option<Ident>,
+    /// `ensure_period` or `ensure_no_period`, the punctuation string
+    /// literal messages are normalized to
+    pub(crate) period: Option<Period>,
+    /// `from_string`, implements `From<String>` and `From<&str>` for
+    /// structs with a single `String` field
+    pub(crate) from_string: Option<Ident>,
+    /// `bind_fields`, binds the fields of a struct as locals in its
+    /// message, like the fields of a variant
+    pub(crate) bind_fields: Option<Ident>,
+    /// `display_chain`, generates a `display_chain` method joining the
+    /// messages of the chain of sources
+    pub(crate) display_chain: bool,
+    /// `chain_sep = " -> "`, the separator of the messages joined by
+    /// `display_chain`
+    pub(crate) chain_sep: Option<LitStr>,
+    /// `display_test`, generates a `#[cfg(test)]` `__display_snapshot`
+    /// method returning the message
+    pub(crate) display_test: bool,
+    /// `json`, writes the message as a JSON object for structured logging
+    pub(crate) json: bool,
+    /// `max_len = 200`, the number of characters messages are truncated to
+    pub(crate) max_len: Option<LitInt>,
+    /// `numbered`, numbers the lines of multiline messages under `{:#}`
+    pub(crate) numbered: bool,
+    /// `escape_control`, escapes the control characters of messages
+    /// except for newlines and tabs
+    pub(crate) escape_control: bool,
+    /// `escape_whitespace`, escapes the control characters of messages
+    /// including newlines and tabs
+    pub(crate) escape_whitespace: bool,
+}
+
+pub(crate) enum Period {
+    Ensure,
+    EnsureNo,
+}
+
+impl Period {
+    /// `message` ending with exactly one period, or without any. Messages
+    /// ending with a `?`, a `!` or an ellipsis, `…` or `...`, are already
+    /// punctuated and left unchanged
+    pub(crate) fn apply(&self, message: &LitStr) -> LitStr {
+        let value = message.value();
+        if value.ends_with(['?', '!', '\u{2026}']) || value.ends_with("...") {
+            return message.to_owned();
+        }
+
+        let value = match self {
+            Self::Ensure if !value.ends_with('.') => value + ".",
+            Self::Ensure => value,
+            Self::EnsureNo => value.trim_end_matches('.').to_owned(),
+        };
+        LitStr::new(&value, message.span())
+    }
+}
+
+impl Options {
+    pub(crate) fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Self::default();
+
+        for attr in attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("error_stack"))
+        {
+            attr.parse_args_with(|input: ParseStream| {
+                while !input.is_empty() {
+                    options.parse_option(input)?;
+
+                    if input.is_empty() {
+                        break;
+                    }
+                    input.parse::<Token![,]>()?;
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+
+    fn parse_option(&mut self, input: ParseStream) -> Result<()> {
+        let key: Ident = input.parse()?;
+
+        match key.to_string().as_str() {
+            "source_fn" => {
+                input.parse::<Token![=]>()?;
+                set(&mut self.source_fn, &key, input.parse()?)
+            }
+            "explicit_source" => set_flag(&mut self.explicit_source, &key),
+            "source_index" => {
+                input.parse::<Token![=]>()?;
+                let index: LitInt = input.parse()?;
+                index.base10_parse::<u32>()?;
+                set(&mut self.source_index, &key, index)
+            }
+            "legacy_cause" => set_flag(&mut self.legacy_cause, &key),
+            "error_cfg" => {
+                input.parse::<Token![=]>()?;
+                let cfg: LitStr = input.parse()?;
+                set(&mut self.error_cfg, &key, cfg.parse()?)
+            }
+            "assert_send_sync" => set_flag(&mut self.assert_send_sync, &key),
+            "kind_enum" => {
+                input.parse::<Token![=]>()?;
+                set(&mut self.kind_enum, &key, input.parse()?)
+            }
+            "constructors" => set(&mut self.constructors, &key, key.to_owned()),
+            "predicates" => set(&mut self.predicates, &key, key.to_owned()),
+            "doc_messages" => set(&mut self.doc_messages, &key, key.to_owned()),
+            "rich_debug" => set_flag(&mut self.rich_debug, &key),
+            "rename" => {
+                input.parse::<Token![=]>()?;
+                set(&mut self.rename, &key, input.parse()?)
+            }
+            "module_prefix" => set_flag(&mut self.module_prefix, &key),
+            "base" => {
+                input.parse::<Token![=]>()?;
+                set(&mut self.base, &key, input.parse()?)
+            }
+            "prefix_with_code" => {
+                let prefix = match input.peek(Token![=]) {
+                    true => {
+                        input.parse::<Token![=]>()?;
+                        input.parse()?
+                    }
+                    false => LitStr::new("E", key.span()),
+                };
+                set(&mut self.prefix_with_code, &key, prefix)
+            }
+            "write_fmt" => set_flag(&mut self.write_fmt, &key),
+            "fmt_with" => {
+                input.parse::<Token![=]>()?;
+                set(&mut self.fmt_with, &key, input.parse()?)
+            }
+            "no_display_impl" => set(&mut self.no_display_impl, &key, key.to_owned()),
+            "align_names" => {
+                input.parse::<Token![=]>()?;
+                let width: LitInt = input.parse()?;
+                width.base10_parse::<usize>()?;
+                set(&mut self.align_names, &key, width)
+            }
+            "discriminant_code" => set(&mut self.discriminant_code, &key, key.to_owned()),
+            "fallback_display" => set(&mut self.fallback_display, &key, key.to_owned()),
+            "display_chain" => set_flag(&mut self.display_chain, &key),
+            "chain_sep" => {
+                input.parse::<Token![=]>()?;
+                set(&mut self.chain_sep, &key, input.parse()?)
+            }
+            "display_test" => set_flag(&mut self.display_test, &key),
+            "json" => set_flag(&mut self.json, &key),
+            "numbered" => set_flag(&mut self.numbered, &key),
+            "escape_control" => set_flag(&mut self.escape_control, &key),
+            "escape_whitespace" => set_flag(&mut self.escape_whitespace, &key),
+            "max_len" => {
+                input.parse::<Token![=]>()?;
+                let max_len: LitInt = input.parse()?;
+                max_len.base10_parse::<usize>()?;
+                set(&mut self.max_len, &key, max_len)
+            }
+            "bind_fields" => set(&mut self.bind_fields, &key, key.to_owned()),
+            "from_string" => set(&mut self.from_string, &key, key.to_owned()),
+            "ensure_period" => set_period(&mut self.period, &key, Period::Ensure),
+            "ensure_no_period" => set_period(&mut self.period, &key, Period::EnsureNo),
+            _ => Err(Error::new(
+                key.span(),
+                format!("unknown error_stack option `{key}`"),
+            )),
+        }
+    }
+}
+
+fn set_period(period: &mut Option<Period>, key: &Ident, value: Period) -> Result<()> {
+    match period {
+        Some(_) => Err(Error::new(
+            key.span(),
+            "only one of `ensure_period` and `ensure_no_period` can be used",
+        )),
+        None => {
+            *period = Some(value);
+            Ok(())
+        }
+    }
+}
+
+fn set_flag(flag: &mut bool, key: &Ident) -> Result<()> {
+    match flag {
+        true => Err(Error::new(
+            key.span(),
+            format!("duplicate error_stack option `{key}`"),
+        )),
+        false => {
+            *flag = true;
+            Ok(())
+        }
+    }
+}
+
+fn set<T>(option: &mut Option<T>, key: &Ident, value: T) -> Result<()> {
+    match option {
+        Some(_) => Err(Error::new(
+            key.span(),
+            format!("duplicate error_stack option `{key}`"),
+        )),
+        None => {
+            *option = Some(value);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Scanning of the `{field}` placeholders of string literal messages
+
+use syn::{Error, Ident, Index, LitStr, Member, Result};
+
+/// A string literal message scanned for its placeholders
+pub(crate) struct Scanned {
+    /// The format string with the field names stripped from its
+    /// placeholders
+    pub(crate) format: LitStr,
+    /// The fields referenced by the placeholders, in order
+    pub(crate) members: Vec<Member>,
+    /// The `name$` width and precision parameters of the format specs
+    pub(crate) params: Vec<Member>,
+}
+
+/// The format string out of `message` with the field names stripped from
+/// its placeholders, along with the referenced fields in order and the
+/// `name$` parameters of their specs. `None` when `message` has no braces
+/// at all, so it can be written as is
+///
+/// `"range {start:#x}..{end:#x}"` becomes `"range {:#x}..{:#x}"` with the
+/// fields `start` and `end`, and `"{0:>1$}"` becomes `"{:>unnamed1$}"` with
+/// the field `0` and the parameter `1`, passed as the named argument
+/// `unnamed1`
+pub(crate) fn scan(message: &LitStr) -> Result<Option<Scanned>> {
+    let value = message.value();
+    if !value.contains(['{', '}']) {
+        return Ok(None);
+    }
+
+    let mut format = String::with_capacity(value.len());
+    let mut members = Vec::new();
+    let mut params = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                format.push_str("{{");
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                format.push_str("}}");
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => {
+                            return Err(Error::new(
+                                message.span(),
+                                "unterminated placeholder, escape a literal `{` as `{{`",
+                            ))
+                        }
+                    }
+                }
+
+                let (name, spec) = match placeholder.split_once(':') {
+                    Some((name, spec)) => (name, Some(spec)),
+                    None => (placeholder.as_str(), None),
+                };
+                members.push(placeholder_member(message, name)?);
+
+                format.push('{');
+                if let Some(spec) = spec {
+                    format.push(':');
+                    format.push_str(&scan_params(message, spec, &mut params)?);
+                }
+                format.push('}');
+            }
+            '}' => {
+                return Err(Error::new(
+                    message.span(),
+                    "unmatched `}`, escape a literal `}` as `}}`",
+                ))
+            }
+            c => format.push(c),
+        }
+    }
+
+    Ok(Some(Scanned {
+        format: LitStr::new(&format, message.span()),
+        members,
+        params,
+    }))
+}
+
+/// The format `spec` with its `name$` width and precision parameters
+/// named after the argument passing them, `unnamed{index}` for field
+/// indexes, which are added to `params` once
+fn scan_params(message: &LitStr, spec: &str, params: &mut Vec<Member>) -> Result<String> {
+    let mut scanned = String::with_capacity(spec.len());
+    let mut rest = spec;
+    while let Some(end) = rest.find('$') {
+        let (before, after) = rest.split_at(end);
+        let start = before
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |pos| pos + 1);
+        let (keep, name) = before.split_at(start);
+        scanned.push_str(keep);
+
+        // A `$` fill character
+        if name.is_empty() {
+            scanned.push('$');
+        } else {
+            let member = placeholder_member(message, name)?;
+            match &member {
+                Member::Named(_) => scanned.push_str(name),
+                Member::Unnamed(index) => scanned.push_str(&format!("unnamed{}", index.index)),
+            }
+            scanned.push('$');
+            if !params.contains(&member) {
+                params.push(member);
+            }
+        }
+        rest = &after[1..];
+    }
+    scanned.push_str(rest);
+
+    Ok(scanned)
+}
+
+/// The field named by the placeholder `{name}`, `{0}` refers to the first
+/// field of a tuple struct or variant and `{type}` to the field `r#type`
+fn placeholder_member(message: &LitStr, name: &str) -> Result<Member> {
+    if name.is_empty() {
+        return Err(Error::new(
+            message.span(),
+            "placeholders have to name a field, like `{field}` or `{0}`",
+        ));
+    }
+
+    if name.bytes().all(|byte| byte.is_ascii_digit()) {
+        let index = name
+            .parse()
+            .map_err(|_| Error::new(message.span(), "field index out of range"))?;
+        return Ok(Member::Unnamed(Index {
+            index,
+            span: message.span(),
+        }));
+    }
+
+    // Keywords can't be placeholder names in `format!`, so `{type}` refers
+    // to the field `r#type`, which can be spelled `{r#type}` too
+    let ident =
+        syn::parse_str::<Ident>(name).or_else(|_| syn::parse_str::<Ident>(&format!("r#{name}")));
+    match ident {
+        Ok(mut ident) => {
+            ident.set_span(message.span());
+            Ok(Member::Named(ident))
+        }
+        Err(_) => Err(Error::new(
+            message.span(),
+            format!("invalid placeholder `{{{name}}}`, expected a field name or index"),
+        )),
+    }
+}
@@ -0,0 +1,186 @@
+//! Handling of the `#[source]`, `#[from]`, `#[related]` and `#[provide]`
+//! field attributes
+
+use syn::{
+    parse::ParseStream, Attribute, Error, ExprClosure, Field, Fields, Ident, Index, LitInt, Member,
+    Result, Token, Type,
+};
+
+/// The field returned by [`std::error::Error::source`], it is either
+/// marked with `#[source]` or, when no field is, with `#[from]`
+pub(crate) struct Source {
+    pub(crate) member: Member,
+    /// The field marked with `#[from]`, if any
+    pub(crate) from: Option<FromField>,
+}
+
+/// A field marked with `#[from]`, a `From<ty>` impl has to be generated
+/// for it
+pub(crate) struct FromField {
+    pub(crate) member: Member,
+    pub(crate) ty: Type,
+    /// `#[from(map = |source| ...)]`, the closure building the variant out
+    /// of the field for variants with more than one field
+    pub(crate) map: Option<ExprClosure>,
+}
+
+impl Source {
+    pub(crate) fn from_fields(fields: &Fields) -> Result<Option<Self>> {
+        let mut source = None;
+        let mut from = None;
+
+        for (pos, field) in fields.iter().enumerate() {
+            let member = member(pos, field);
+
+            for attr in &field.attrs {
+                if attr.path.is_ident("source") {
+                    if source.is_some() {
+                        return Err(Error::new_spanned(
+                            attr,
+                            "only one field can be marked as the source",
+                        ));
+                    }
+                    source = Some(member.to_owned());
+                } else if attr.path.is_ident("from") {
+                    if from.is_some() {
+                        return Err(Error::new_spanned(
+                            attr,
+                            "only one field can be marked with #[from]",
+                        ));
+                    }
+
+                    let map = parse_map(attr)?;
+                    if map.is_none() && fields.len() != 1 {
+                        return Err(Error::new_spanned(
+                            attr,
+                            "#[from] requires the struct or the variant to have exactly one field, \
+                             use #[from(map = |source| ...)] to build the other fields",
+                        ));
+                    }
+
+                    from = Some(FromField {
+                        member: member.to_owned(),
+                        ty: field.ty.to_owned(),
+                        map,
+                    });
+                }
+            }
+        }
+
+        // A `#[from]` field is the source, unless another field is explicitly
+        // marked as the source
+        Ok(match (source, from) {
+            (Some(member), from) => Some(Self { member, from }),
+            (None, Some(from)) => Some(Self {
+                member: from.member.to_owned(),
+                from: Some(from),
+            }),
+            (None, None) => None,
+        })
+    }
+
+    /// The field at `index` of a tuple variant, for
+    /// `#[error_stack(source_index = ...)]`
+    pub(crate) fn from_index(fields: &Fields, index: &LitInt) -> Result<Option<Self>> {
+        let index = Index {
+            index: index.base10_parse()?,
+            span: index.span(),
+        };
+
+        Ok(match fields {
+            Fields::Unnamed(fields) if (index.index as usize) < fields.unnamed.len() => {
+                Some(Self {
+                    member: Member::Unnamed(index),
+                    from: None,
+                })
+            }
+            _ => None,
+        })
+    }
+}
+
+/// The field marked with `#[related]`, a collection of boxed errors, like
+/// `Vec<Box<dyn std::error::Error + Send + Sync>>`, exposed through a
+/// generated `related` method
+pub(crate) fn related_field(fields: &Fields) -> Result<Option<Member>> {
+    let mut related = None;
+
+    for (pos, field) in fields.iter().enumerate() {
+        if let Some(attr) = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("related"))
+        {
+            if related.is_some() {
+                return Err(Error::new_spanned(
+                    attr,
+                    "only one field can be marked with #[related]",
+                ));
+            }
+            related = Some(member(pos, field));
+        }
+    }
+
+    Ok(related)
+}
+
+/// The fields marked with `#[provide]`, provided by reference through
+/// [`std::error::Error::provide`] with the `provide` feature
+pub(crate) fn provided_fields(fields: &Fields) -> Result<Vec<(Member, Type)>> {
+    let mut provided = Vec::new();
+
+    for (pos, field) in fields.iter().enumerate() {
+        if let Some(attr) = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("provide"))
+        {
+            if !cfg!(feature = "provide") {
+                return Err(Error::new_spanned(
+                    attr,
+                    "#[provide] requires the `provide` feature of error-stack-derive",
+                ));
+            }
+            provided.push((member(pos, field), field.ty.to_owned()));
+        }
+    }
+
+    Ok(provided)
+}
+
+/// The member accessing `field`, the `pos`th field of its struct or variant
+pub(crate) fn member(pos: usize, field: &Field) -> Member {
+    match &field.ident {
+        Some(ident) => Member::Named(ident.to_owned()),
+        None => Member::Unnamed(pos.into()),
+    }
+}
+
+/// The closure out of `#[from(map = |source| ...)]`, `None` for `#[from]`
+fn parse_map(attr: &Attribute) -> Result<Option<ExprClosure>> {
+    if attr.tokens.is_empty() {
+        return Ok(None);
+    }
+
+    attr.parse_args_with(|input: ParseStream| {
+        let key = input.parse::<Ident>()?;
+        if key != "map" {
+            return Err(Error::new(
+                key.span(),
+                format!("unknown from option `{key}`, expected `map`"),
+            ));
+        }
+        input.parse::<Token![=]>()?;
+
+        let map = input.parse::<ExprClosure>()?;
+        if map.inputs.len() != 1 {
+            return Err(Error::new_spanned(
+                &map.inputs,
+                "expected a closure taking the converted value, \
+                 like `|source| Self::Variant { source, .. }`",
+            ));
+        }
+
+        Ok(Some(map))
+    })
+}
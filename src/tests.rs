@@ -0,0 +1,270 @@
+//! Expansions of many item shapes, checked without a compiler
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use crate::{derive, expand_errors};
+
+/// The items every option is combined with
+const SHAPES: &[&str] = &[
+    r#"#[error_message("unit")] struct E;"#,
+    r#"#[error_message("tuple {0}")] struct E(u8);"#,
+    r#"#[error_message(&format!("{}", self.0))] struct E(u8, String, #[source] std::io::Error);"#,
+    r#"#[error_message("named {line}")] struct E { line: usize, r#type: u8 }"#,
+    r#"#[error_message(debug)] struct E<'a, T: Debug, const N: usize>(&'a [T; N]) where T: Clone;"#,
+    r#"#[error_message(transparent)] struct E(#[from] std::io::Error);"#,
+    r#"#[error_message(display = inner)] struct E { inner: std::fmt::Error }"#,
+    r#"#[error_message(pad("padded"))] struct E;"#,
+    r#"#[error_message("{0:>1$.p$}", p = 2)] struct E(f32, usize);"#,
+    r#"enum E {}"#,
+    r#"enum E { Unit, Tuple(u8), Named { line: usize } }"#,
+    r#"#[error_message("default")] enum E {
+        #[error_message("unit")] Unit,
+        #[error_message("{0} {1}")] Tuple(u8, u8),
+        #[error_message(&format!("{line}"))] Named { line: usize },
+        /// Documented, with {braces}
+        Fallback,
+    }"#,
+    r#"enum E<T: Error + 'static> {
+        #[error_message("io")] Io(#[from] std::io::Error),
+        #[error_message(transparent)] Inner(T),
+        #[error_message(debug)] Debug { #[source] source: std::fmt::Error },
+        #[cfg(unix)] #[error_message(display_expr = self.name())] Cfg,
+    }"#,
+];
+
+/// The options added to every shape they apply to
+const OPTIONS: &[&str] = &[
+    "",
+    "explicit_source",
+    "legacy_cause",
+    "assert_send_sync",
+    "rich_debug",
+    r#"rename = "Renamed""#,
+    "module_prefix",
+    "ensure_period",
+    "ensure_no_period",
+    "display_chain",
+    r#"chain_sep = " -> ""#,
+    "display_test",
+    "json",
+    "max_len = 10",
+    "numbered",
+    "escape_control",
+    "escape_whitespace, numbered",
+    r#"base = "Base""#,
+    "json, max_len = 4, module_prefix, display_chain",
+    r#"error_cfg = "feature = \"std\"""#,
+    "kind_enum = Kind",
+    "discriminant_code",
+    "constructors",
+    "predicates",
+    "doc_messages",
+    "fallback_display",
+    "source_index = 0",
+    "bind_fields",
+];
+
+fn expand(input: &str) -> TokenStream2 {
+    derive(syn::parse_str(input).expect("input"))
+}
+
+/// Whether `tokens`, which have to parse as items, are the generated impls
+/// rather than compile errors
+fn is_valid(input: &str, tokens: TokenStream2) -> bool {
+    let file: syn::File = syn::parse2(tokens.to_owned())
+        .unwrap_or_else(|err| panic!("unparsable expansion of `{input}`: {err}\n{tokens}"));
+    !file.items.iter().any(|item| match item {
+        syn::Item::Macro(item) => item
+            .mac
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "compile_error"),
+        _ => false,
+    })
+}
+
+#[test]
+fn shapes_with_options_expand_to_items() {
+    let mut valid = 0;
+    for shape in SHAPES {
+        for option in OPTIONS {
+            let input = format!("#[error_stack({option})] {shape}");
+            if is_valid(&input, expand(&input)) {
+                valid += 1;
+            }
+        }
+    }
+
+    // The others are reported as compile errors, like the options only
+    // applying to structs used on enums. A shape or an option added to the
+    // lists changes the count
+    assert_eq!(valid, 290, "{valid} valid expansions");
+}
+
+#[test]
+fn valid_shapes_expand_without_errors() {
+    for shape in SHAPES {
+        assert!(is_valid(shape, expand(shape)), "`{shape}` is rejected");
+    }
+}
+
+#[test]
+fn invalid_shapes_expand_to_compile_errors() {
+    let inputs = [
+        "struct E;",
+        "#[error_message] struct E;",
+        r#"#[error_message("{missing}")] struct E;"#,
+        r#"#[error_message("{")] struct E;"#,
+        r#"#[error_message("{0}")] enum E { A(u8) }"#,
+        r#"#[error_message("{0:w$}")] struct E(u8);"#,
+        r#"#[error_message("e", unused = 1)] struct E;"#,
+        r#"#[error_message("value {}", self.0)] struct E(u8);"#,
+        r#"#[error_message(pad("value {}", self.0))] struct E(u8);"#,
+        r#"#[error_stack(unknown)] #[error_message("e")] struct E;"#,
+        r#"#[error_stack(kind_enum = Kind)] #[error_message("e")] struct E;"#,
+        r#"#[error_message(transparent)] struct E(u8, u8);"#,
+        "#[error_stack(discriminant_code)] enum E { A(u8) }",
+        "#[error_stack(constructors)] enum E { IoError, IOError }",
+        "#[error_stack(constructors, kind_enum = K)] enum E { Kind }",
+        "#[error_stack(predicates, constructors)] enum E { IsA, A }",
+        "#[error_stack(constructors)] enum E { Super }",
+        "#[error_stack(constructors)] enum E { Crate }",
+        "#[error_stack(constructors)] enum E { SELF }",
+        "#[error_stack(constructors)] enum E { Related(#[related] Vec<std::io::Error>) }",
+        r#"#[error_stack(align_names = 8)] #[error_message("default")] enum E { A }"#,
+        r#"#[error_stack(prefix_with_code)] #[error_message("e")] struct E;"#,
+        r#"struct E { #[error_message("field")] field: u8 }"#,
+        r#"#[error_message("union")] union E { a: u8, b: u16 }"#,
+    ];
+
+    for input in inputs {
+        assert!(!is_valid(input, expand(input)), "`{input}` is accepted");
+    }
+}
+
+#[test]
+fn errors_list_expands_to_items() {
+    let tokens = expand_errors(syn::parse_str(r#"A => "a", pub B => "b","#).expect("input"));
+    assert!(is_valid("errors!", tokens));
+
+    let tokens = expand_errors(syn::parse_str("A =>").expect("input"));
+    assert!(!is_valid("errors!", tokens));
+}
+
+#[test]
+fn struct_expansion_snapshot() {
+    let expansion = expand(r#"#[error_message("Not found {0}")] struct NotFoundError(u16);"#);
+    let expected = quote! {
+        impl ::std::fmt::Display for NotFoundError {
+            fn fmt(&self, fmt: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                fmt.write_fmt(::std::format_args!("Not found {}", &self.0))
+            }
+        }
+
+        impl ::std::error::Error for NotFoundError {}
+    };
+
+    assert_eq!(expansion.to_string(), expected.to_string());
+}
+
+#[test]
+fn enum_expansion_snapshot() {
+    // The variants with a message in declaration order, then the catch-all
+    // arm of the ones falling back to the default message
+    let expansion = expand(
+        r#"enum E {
+            Unit,
+            #[error_message("tuple {0}")] Tuple(u8),
+            Named { line: usize },
+            #[error_message("last")] Last,
+        }"#,
+    );
+    let expected = quote! {
+        impl ::std::fmt::Display for E {
+            fn fmt(&self, fmt: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match self {
+                    Self::Tuple(unnamed0) => fmt.write_fmt(::std::format_args!("tuple {}", unnamed0)),
+                    Self::Last => match ("last") {
+                        message => fmt.write_str(::std::convert::AsRef::<str>::as_ref(&message)),
+                    },
+                    _ => match (::std::format!("[{name}] An error occured; {:?}", self, name = "E",)) {
+                        message => fmt.write_str(::std::convert::AsRef::<str>::as_ref(&message)),
+                    },
+                }
+            }
+        }
+
+        impl ::std::error::Error for E {}
+
+        const _: () = {
+            fn assert_debug<T: ?::std::marker::Sized + ::std::fmt::Debug>() {}
+
+            #[allow(dead_code)]
+            fn assert() {
+                assert_debug::<E>();
+            }
+        };
+    };
+
+    assert_eq!(expansion.to_string(), expected.to_string());
+}
+
+#[test]
+fn covered_enum_expansion_has_no_catch_all() {
+    // Every variant has a message, a catch-all arm would be unreachable
+    let expansion = expand(
+        r#"enum E {
+            #[error_message("unit")] Unit,
+            #[error_message("tuple {0}")] Tuple(u8),
+        }"#,
+    );
+    let expected = quote! {
+        impl ::std::fmt::Display for E {
+            fn fmt(&self, fmt: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match self {
+                    Self::Unit => match ("unit") {
+                        message => fmt.write_str(::std::convert::AsRef::<str>::as_ref(&message)),
+                    },
+                    Self::Tuple(unnamed0) => fmt.write_fmt(::std::format_args!("tuple {}", unnamed0)),
+                }
+            }
+        }
+
+        impl ::std::error::Error for E {}
+    };
+
+    assert_eq!(expansion.to_string(), expected.to_string());
+}
+
+#[test]
+fn enum_expansion_binds_referenced_fields() {
+    // The fields the messages don't reference are `_` or left to `..`
+    let expansion = expand(
+        r#"enum E {
+            #[error_message("last {2}")] Tuple(u8, u16, u32),
+            #[error_message(&format!("{a}"))] Named { a: u8, b: u8 },
+            #[error_message("none")] Unused { a: u8 },
+        }"#,
+    );
+    let expected = quote! {
+        impl ::std::fmt::Display for E {
+            fn fmt(&self, fmt: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match self {
+                    Self::Tuple(_, _, unnamed2) => fmt.write_fmt(::std::format_args!("last {}", unnamed2)),
+                    Self::Named { a, .. } => match (&format!("{a}")) {
+                        message => fmt.write_str(::std::convert::AsRef::<str>::as_ref(&message)),
+                    },
+                    Self::Unused { .. } => match ("none") {
+                        message => fmt.write_str(::std::convert::AsRef::<str>::as_ref(&message)),
+                    },
+                }
+            }
+        }
+
+        impl ::std::error::Error for E {}
+    };
+
+    assert_eq!(expansion.to_string(), expected.to_string());
+}
@@ -0,0 +1,1729 @@
+#![allow(dead_code)]
+
+use error_stack_derive::ErrorStack;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("unhandled: {:?}", self))]
+enum FallthroughError {
+    #[error_message("handled")]
+    Handled,
+    Unit,
+    Tuple(u8, &'static str),
+    Named {
+        fmt: u8,
+    },
+}
+
+#[test]
+fn default_message_can_reference_self() {
+    assert_eq!(FallthroughError::Handled.to_string(), "handled");
+    assert_eq!(FallthroughError::Unit.to_string(), "unhandled: Unit");
+    assert_eq!(
+        FallthroughError::Tuple(1, "a").to_string(),
+        r#"unhandled: Tuple(1, "a")"#
+    );
+    assert_eq!(
+        FallthroughError::Named { fmt: 2 }.to_string(),
+        "unhandled: Named { fmt: 2 }"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+enum DebugError {
+    #[error_message(debug)]
+    Tuple(u8, String),
+    #[error_message(debug)]
+    Named { line: usize, column: usize },
+    #[error_message(debug)]
+    Unit,
+}
+
+#[test]
+fn debug_message_writes_debug_representation() {
+    let tuple = DebugError::Tuple(1, "a".to_owned());
+    assert_eq!(tuple.to_string(), format!("{tuple:?}"));
+    assert_eq!(tuple.to_string(), r#"Tuple(1, "a")"#);
+
+    let named = DebugError::Named { line: 1, column: 2 };
+    assert_eq!(named.to_string(), "Named { line: 1, column: 2 }");
+
+    assert_eq!(DebugError::Unit.to_string(), "Unit");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(format!("owned default {:?}", self))]
+enum OwnedMessageError {
+    #[error_message("borrowed")]
+    Borrowed,
+    #[error_message(format!("owned {unnamed0}"))]
+    Owned(u8),
+    Default,
+}
+
+#[test]
+fn messages_can_be_str_or_string() {
+    assert_eq!(OwnedMessageError::Borrowed.to_string(), "borrowed");
+    assert_eq!(OwnedMessageError::Owned(1).to_string(), "owned 1");
+    assert_eq!(
+        OwnedMessageError::Default.to_string(),
+        "owned default Default"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(source_fn = my_source)]
+enum SourceFnError {
+    Io(std::io::Error),
+    Other,
+}
+
+impl SourceFnError {
+    fn my_source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Other => None,
+        }
+    }
+}
+
+#[test]
+fn source_fn_is_used_as_source() {
+    use std::error::Error;
+
+    let err = SourceFnError::Io(std::io::ErrorKind::NotFound.into());
+    assert!(err.source().unwrap().is::<std::io::Error>());
+    assert!(SourceFnError::Other.source().is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+enum CfgError {
+    #[cfg(not(test))]
+    #[error_message(&format!("disabled {}", unnamed0))]
+    Disabled(NotDisplay),
+    #[cfg(test)]
+    #[error_message("enabled")]
+    Enabled,
+    #[cfg_attr(test, error_message("enabled by cfg_attr"))]
+    CfgAttr,
+    #[cfg_attr(not(test), error_message("disabled by cfg_attr"))]
+    NoCfgAttr,
+}
+
+#[derive(Debug)]
+struct NotDisplay;
+
+#[test]
+fn cfg_disabled_variants_have_no_arm() {
+    assert_eq!(CfgError::Enabled.to_string(), "enabled");
+    assert_eq!(CfgError::CfgAttr.to_string(), "enabled by cfg_attr");
+    assert_eq!(
+        CfgError::NoCfgAttr.to_string(),
+        "[CfgError] An error occured; NoCfgAttr"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+enum SourceError {
+    #[error_message("io")]
+    Io(#[from] std::io::Error),
+    #[error_message("fmt")]
+    Fmt(#[source] std::fmt::Error, u8),
+    #[error_message("named")]
+    Named {
+        code: u8,
+        #[source]
+        inner: OwnedMessageError,
+    },
+    #[error_message("none")]
+    None,
+}
+
+#[test]
+fn from_field_is_the_source() {
+    use std::error::Error;
+
+    let err = SourceError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+    assert!(matches!(err, SourceError::Io(_)));
+    let source = err.source().expect("source");
+    let io = source.downcast_ref::<std::io::Error>().expect("io::Error");
+    assert_eq!(io.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn source_fields_are_the_source() {
+    use std::error::Error;
+
+    let fmt = SourceError::Fmt(std::fmt::Error, 0);
+    assert!(fmt.source().unwrap().is::<std::fmt::Error>());
+
+    let named = SourceError::Named {
+        code: 0,
+        inner: OwnedMessageError::Borrowed,
+    };
+    assert_eq!(named.source().unwrap().to_string(), "borrowed");
+
+    assert!(SourceError::None.source().is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(source_index = 0)]
+enum IndexedSourceError {
+    #[error_message("io")]
+    Io(std::io::Error),
+    #[error_message("fmt")]
+    Fmt(std::fmt::Error, u8),
+    #[error_message("parse")]
+    Parse(std::num::ParseIntError),
+    #[error_message("marked")]
+    Marked(u8, #[source] std::fmt::Error),
+    #[error_message("unit")]
+    Unit,
+}
+
+#[test]
+fn source_index_is_the_source_of_tuple_variants() {
+    use std::error::Error;
+
+    let io = IndexedSourceError::Io(std::io::ErrorKind::NotFound.into());
+    assert!(io.source().unwrap().is::<std::io::Error>());
+    let fmt = IndexedSourceError::Fmt(std::fmt::Error, 0);
+    assert!(fmt.source().unwrap().is::<std::fmt::Error>());
+    let parse = IndexedSourceError::Parse("x".parse::<u8>().unwrap_err());
+    assert!(parse.source().unwrap().is::<std::num::ParseIntError>());
+    let marked = IndexedSourceError::Marked(0, std::fmt::Error);
+    assert!(marked.source().unwrap().is::<std::fmt::Error>());
+    assert!(IndexedSourceError::Unit.source().is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(legacy_cause)]
+enum LegacyCauseError {
+    #[error_message("fmt")]
+    Fmt(#[source] std::fmt::Error),
+    #[error_message("none")]
+    None,
+}
+
+#[test]
+#[allow(deprecated)]
+fn cause_returns_the_source() {
+    use std::error::Error;
+
+    let err = LegacyCauseError::Fmt(std::fmt::Error);
+    assert_eq!(
+        err.cause().unwrap().to_string(),
+        std::fmt::Error.to_string()
+    );
+    assert!(LegacyCauseError::None.cause().is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(module_prefix)]
+enum ModulePrefixError {
+    #[error_message("refused")]
+    Refused,
+    #[error_message(debug)]
+    Timeout(u8),
+    Other,
+}
+
+#[test]
+fn module_prefix_prepends_module_path() {
+    assert_eq!(ModulePrefixError::Refused.to_string(), "enums: refused");
+    assert_eq!(
+        ModulePrefixError::Timeout(1).to_string(),
+        "enums: Timeout(1)"
+    );
+    assert_eq!(
+        ModulePrefixError::Other.to_string(),
+        "enums: [ModulePrefixError] An error occured; Other"
+    );
+    assert_eq!(OwnedMessageError::Borrowed.to_string(), "borrowed");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("default {:?}", self)]
+#[error_stack(write_fmt)]
+enum WriteFmtError {
+    #[error_message("tuple {unnamed0} {}", unnamed1)]
+    Tuple(u8, u8),
+    #[error_message("named {line}:{column}")]
+    Named {
+        line: usize,
+        column: usize,
+    },
+    #[error_message(debug)]
+    Debug,
+    Default,
+}
+
+#[test]
+fn write_fmt_formats_arguments() {
+    assert_eq!(WriteFmtError::Tuple(1, 2).to_string(), "tuple 1 2");
+    assert_eq!(
+        WriteFmtError::Named { line: 3, column: 4 }.to_string(),
+        "named 3:4"
+    );
+    assert_eq!(WriteFmtError::Debug.to_string(), "Debug");
+    assert_eq!(WriteFmtError::Default.to_string(), "default Default");
+}
+
+#[deny(warnings)]
+mod strict {
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message("unreachable default")]
+    pub enum CoveredError {
+        #[error_message("first")]
+        First,
+        #[error_message(debug)]
+        Second(u8),
+    }
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message("reachable default")]
+    #[non_exhaustive]
+    pub enum NonExhaustiveError {
+        #[error_message("first")]
+        First,
+    }
+
+    #[derive(ErrorStack, Debug)]
+    pub enum EmptyError {}
+}
+
+#[test]
+fn fully_covered_enum_has_no_catch_all() {
+    assert_eq!(strict::CoveredError::First.to_string(), "first");
+    assert_eq!(strict::CoveredError::Second(1).to_string(), "Second(1)");
+    assert_eq!(strict::NonExhaustiveError::First.to_string(), "first");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(fmt_with = Self::render, module_prefix)]
+enum FmtWithError {
+    Nested(OwnedMessageError),
+    Unit,
+}
+
+impl FmtWithError {
+    fn render(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nested(inner) => write!(fmt, "nested: {inner}"),
+            Self::Unit => fmt.write_str("unit"),
+        }
+    }
+}
+
+#[test]
+fn fmt_with_renders_through_the_formatter() {
+    assert_eq!(
+        FmtWithError::Nested(OwnedMessageError::Owned(1)).to_string(),
+        "enums: nested: owned 1"
+    );
+    assert_eq!(FmtWithError::Unit.to_string(), "enums: unit");
+}
+
+#[derive(ErrorStack, Debug)]
+enum MappedFromError {
+    #[error_message(&format!("parse error at {path}:{line}"))]
+    Parse {
+        #[from(map = |source| Self::Parse { source, path: "<unknown>", line: 0 })]
+        source: std::io::Error,
+        path: &'static str,
+        line: u32,
+    },
+}
+
+#[test]
+fn from_map_builds_multi_field_variant() {
+    use std::error::Error;
+
+    let err: MappedFromError = std::io::Error::from(std::io::ErrorKind::InvalidData).into();
+    assert_eq!(err.to_string(), "parse error at <unknown>:0");
+    let source = err.source().unwrap().downcast_ref::<std::io::Error>();
+    assert_eq!(source.unwrap().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(align_names = 12)]
+enum AlignedError {
+    NotFound,
+    PermissionDenied(u8),
+    #[error_message("dedicated")]
+    Dedicated,
+}
+
+#[test]
+fn align_names_pads_variant_names() {
+    assert_eq!(
+        AlignedError::NotFound.to_string(),
+        "[AlignedError] NotFound     An error occured; NotFound"
+    );
+    // Longer names than the width are never truncated
+    assert_eq!(
+        AlignedError::PermissionDenied(1).to_string(),
+        "[AlignedError] PermissionDenied An error occured; PermissionDenied(1)"
+    );
+    assert_eq!(AlignedError::Dedicated.to_string(), "dedicated");
+    assert_eq!(
+        CfgError::NoCfgAttr.to_string(),
+        "[CfgError] An error occured; NoCfgAttr"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+enum TrailingCommaError {
+    #[error_message(&format!("a={}, b={}", a, b,))]
+    Named { a: u8, b: u8 },
+}
+
+#[test]
+fn trailing_commas_in_format_arguments() {
+    assert_eq!(
+        TrailingCommaError::Named { a: 1, b: 2 }.to_string(),
+        "a=1, b=2"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+enum AggregateError {
+    #[error_message("many")]
+    Many(#[related] Vec<Box<dyn std::error::Error + Send + Sync>>),
+    #[error_message("boxed")]
+    Boxed {
+        #[related]
+        errors: Box<[Box<dyn std::error::Error>]>,
+    },
+    #[error_message("none")]
+    None,
+}
+
+#[test]
+fn related_iterates_over_aggregated_errors() {
+    let many = AggregateError::Many(vec![
+        Box::new(OwnedMessageError::Borrowed),
+        Box::new(OwnedMessageError::Owned(1)),
+    ]);
+    let related = many
+        .related()
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(related, ["borrowed", "owned 1"]);
+
+    let boxed = AggregateError::Boxed {
+        errors: Box::new([Box::new(OwnedMessageError::Borrowed) as _]),
+    };
+    assert_eq!(boxed.related().count(), 1);
+
+    assert_eq!(AggregateError::None.related().count(), 0);
+}
+
+mod outer {
+    pub mod middle {
+        pub mod inner {
+            use error_stack_derive::ErrorStack;
+
+            #[derive(ErrorStack, Debug)]
+            #[error_stack(module_prefix)]
+            pub enum NestedError {
+                #[error_message(&format!("nested {unnamed0}"))]
+                Nested(u8),
+                Io(#[from] std::io::Error),
+            }
+        }
+    }
+}
+
+#[test]
+fn derives_in_nested_modules() {
+    use std::error::Error;
+
+    use outer::middle::inner::NestedError;
+
+    assert_eq!(
+        NestedError::Nested(1).to_string(),
+        "enums::outer::middle::inner: nested 1"
+    );
+    let io = NestedError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+    assert!(io.source().is_some());
+}
+
+#[test]
+fn derives_in_function_bodies() {
+    use std::error::Error;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message(&format!("local default {:?}", self))]
+    enum LocalError {
+        #[error_message(&format!("local {name}"))]
+        Named {
+            name: &'static str,
+        },
+        Io(#[from] std::io::Error),
+        Unit,
+    }
+
+    assert_eq!(LocalError::Named { name: "a" }.to_string(), "local a");
+    assert_eq!(LocalError::Unit.to_string(), "local default Unit");
+    let io = LocalError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+    assert!(io.source().is_some());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("default")]
+#[error_stack(ensure_period)]
+enum PeriodError {
+    #[error_message("with period.")]
+    With,
+    #[error_message("without period")]
+    Without,
+    #[error_message("Really?")]
+    Question,
+    #[error_message("Careful!")]
+    Exclamation,
+    #[error_message("Wait\u{2026}")]
+    Ellipsis,
+    #[error_message(&format!("expression {}", unnamed0))]
+    Expression(u8),
+    Default,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("default.")]
+#[error_stack(ensure_no_period)]
+enum NoPeriodError {
+    #[error_message("with period.")]
+    With,
+    #[error_message("without period")]
+    Without,
+    #[error_message("ellipsis...")]
+    Ellipsis,
+    #[error_message("periods..")]
+    Periods,
+    #[error_message("Really?")]
+    Question,
+    Default,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(write_fmt, ensure_period)]
+enum WriteFmtPeriodError {
+    #[error_message("format {}", unnamed0)]
+    Format(u8),
+    #[error_message("format {}.", unnamed0)]
+    FormatWith(u8),
+}
+
+#[test]
+fn ensure_period_appends_missing_periods() {
+    assert_eq!(PeriodError::With.to_string(), "with period.");
+    assert_eq!(PeriodError::Without.to_string(), "without period.");
+    assert_eq!(PeriodError::Question.to_string(), "Really?");
+    assert_eq!(PeriodError::Exclamation.to_string(), "Careful!");
+    assert_eq!(PeriodError::Ellipsis.to_string(), "Wait\u{2026}");
+    assert_eq!(PeriodError::Expression(1).to_string(), "expression 1");
+    assert_eq!(PeriodError::Default.to_string(), "default.");
+    assert_eq!(WriteFmtPeriodError::Format(1).to_string(), "format 1.");
+    assert_eq!(WriteFmtPeriodError::FormatWith(1).to_string(), "format 1.");
+}
+
+#[test]
+fn ensure_no_period_trims_trailing_periods() {
+    assert_eq!(NoPeriodError::With.to_string(), "with period");
+    assert_eq!(NoPeriodError::Without.to_string(), "without period");
+    assert_eq!(NoPeriodError::Ellipsis.to_string(), "ellipsis...");
+    assert_eq!(NoPeriodError::Periods.to_string(), "periods");
+    assert_eq!(NoPeriodError::Question.to_string(), "Really?");
+    assert_eq!(NoPeriodError::Default.to_string(), "default");
+}
+
+#[derive(ErrorStack, Debug)]
+enum DisplayFieldError {
+    #[error_message(display = 1)]
+    Tuple(u8, &'static str),
+    #[error_message(display = inner)]
+    Named { code: u8, inner: OwnedMessageError },
+}
+
+#[test]
+fn display_forwards_to_the_field() {
+    assert_eq!(DisplayFieldError::Tuple(0, "tuple").to_string(), "tuple");
+    assert_eq!(
+        format!("{:<7}|", DisplayFieldError::Tuple(0, "flag")),
+        "flag   |"
+    );
+    let named = DisplayFieldError::Named {
+        code: 0,
+        inner: OwnedMessageError::Owned(2),
+    };
+    assert_eq!(named.to_string(), "owned 2");
+}
+
+#[derive(ErrorStack, Debug)]
+enum DisplayExprError {
+    #[error_message(display_expr = self.inner())]
+    Inner(u8),
+    #[error_message(display_expr = name.escape_default())]
+    Named { name: &'static str },
+}
+
+impl DisplayExprError {
+    fn inner(&self) -> impl std::fmt::Display {
+        match self {
+            Self::Inner(code) => *code,
+            Self::Named { .. } => 0,
+        }
+    }
+}
+
+#[test]
+fn display_expr_forwards_to_the_expression() {
+    assert_eq!(DisplayExprError::Inner(7).to_string(), "7");
+    assert_eq!(format!("{:03}", DisplayExprError::Inner(7)), "007");
+    let named = DisplayExprError::Named { name: "a\nb" };
+    assert_eq!(named.to_string(), "a\\nb");
+}
+
+#[derive(ErrorStack, Debug)]
+enum ShadowingError {
+    #[error_message("error variant")]
+    Error(#[from] std::io::Error),
+    #[error_message(debug)]
+    Display,
+    Result,
+}
+
+#[test]
+fn variants_named_like_std_items_do_not_conflict() {
+    use std::error::Error;
+
+    let err = ShadowingError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+    assert!(matches!(err, ShadowingError::Error(_)));
+    assert_eq!(err.to_string(), "error variant");
+    assert!(err.source().unwrap().is::<std::io::Error>());
+    assert_eq!(ShadowingError::Display.to_string(), "Display");
+    assert!(ShadowingError::Result.source().is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(display_chain)]
+enum ChainInnerError {
+    #[error_message("middle")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(display_chain)]
+enum ChainOuterError {
+    #[error_message("outer")]
+    Inner(#[from] ChainInnerError),
+    #[error_message("leaf")]
+    Leaf,
+}
+
+#[test]
+fn display_chain_joins_the_source_chain() {
+    let io = std::io::Error::other("inner");
+    let err = ChainOuterError::from(ChainInnerError::from(io));
+    assert_eq!(err.display_chain(), "outer: middle: inner");
+    assert_eq!(ChainOuterError::Leaf.display_chain(), "leaf");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(chain_sep = " -> ")]
+enum ArrowChainError {
+    #[error_message("outer")]
+    Inner(#[from] ChainInnerError),
+}
+
+#[test]
+fn chain_sep_joins_the_source_chain() {
+    let io = std::io::Error::other("inner");
+    let err = ArrowChainError::from(ChainInnerError::from(io));
+    assert_eq!(err.display_chain(), "outer -> middle -> inner");
+}
+
+#[derive(ErrorStack, Debug)]
+enum QuxError {
+    #[error_message("range {start:#x}..{end:#x}")]
+    Range { start: u64, end: u64 },
+    #[error_message("unexpected {token:?}")]
+    Token { token: String },
+    #[error_message("[{code:>4}] {code:04}")]
+    Code { code: u16 },
+    #[error_message("{0} {{literal}}")]
+    Tuple(u8),
+}
+
+#[test]
+fn placeholders_keep_their_format_spec() {
+    assert_eq!(
+        QuxError::Range {
+            start: 255,
+            end: 4096
+        }
+        .to_string(),
+        "range 0xff..0x1000"
+    );
+    assert_eq!(
+        QuxError::Token {
+            token: String::from("}")
+        }
+        .to_string(),
+        "unexpected \"}\""
+    );
+    assert_eq!(QuxError::Code { code: 42 }.to_string(), "[  42] 0042");
+    assert_eq!(QuxError::Tuple(7).to_string(), "7 {literal}");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(display_test)]
+#[error_message("snapshot fallback")]
+enum SnapshotError {
+    #[error_message("snapshot {0}")]
+    Tuple(u8),
+    #[error_message("snapshot {name}")]
+    Named {
+        name: &'static str,
+    },
+    Unit,
+}
+
+#[test]
+fn display_snapshot_of_every_variant() {
+    assert_eq!(SnapshotError::Tuple(1).__display_snapshot(), "snapshot 1");
+    assert_eq!(
+        SnapshotError::Named { name: "foo" }.__display_snapshot(),
+        "snapshot foo"
+    );
+    assert_eq!(
+        SnapshotError::Unit.__display_snapshot(),
+        "snapshot fallback"
+    );
+}
+
+#[deny(unused_variables)]
+mod sparse_fields {
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    pub enum SparseError {
+        #[error_message(&format!("last {}", unnamed2))]
+        Expr(u8, u16, u32),
+        #[error_message("last {2}")]
+        Placeholder(u8, u16, u32),
+        #[error_message("first and last {0} {2}")]
+        Gap(u8, u16, u32),
+    }
+}
+
+#[test]
+fn sparse_tuple_fields() {
+    use sparse_fields::SparseError;
+
+    assert_eq!(SparseError::Expr(1, 2, 3).to_string(), "last 3");
+    assert_eq!(SparseError::Placeholder(1, 2, 3).to_string(), "last 3");
+    assert_eq!(SparseError::Gap(1, 2, 3).to_string(), "first and last 1 3");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(json)]
+#[error_message("fallback")]
+enum JsonError {
+    #[error_message("quoted \"{0}\"")]
+    Quoted(&'static str),
+    #[error_message("path C:\\{path}\nline\t{line}")]
+    Escaped {
+        path: &'static str,
+        line: u8,
+    },
+    #[error_message("bell \u{7}")]
+    Control,
+    Unit,
+}
+
+#[test]
+fn json_display_is_valid_json() {
+    let cases = [
+        (JsonError::Quoted("foo"), "Quoted", "quoted \"foo\""),
+        (
+            JsonError::Escaped {
+                path: "tmp",
+                line: 3,
+            },
+            "Escaped",
+            "path C:\\tmp\nline\t3",
+        ),
+        (JsonError::Control, "Control", "bell \u{7}"),
+        (JsonError::Unit, "Unit", "fallback"),
+    ];
+
+    for (err, name, message) in cases {
+        let json: serde_json::Value = serde_json::from_str(&err.to_string()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "error": name, "message": message })
+        );
+    }
+}
+
+#[derive(ErrorStack, Debug)]
+enum DefaultedError<T: std::fmt::Debug = String, const N: usize = 2> {
+    #[error_message("defaulted {0:?}")]
+    Value(T),
+    #[error_message("array {0:?}")]
+    Array([u8; N]),
+}
+
+#[test]
+fn default_type_parameters() {
+    let err: DefaultedError = DefaultedError::Value(String::from("foo"));
+    assert_eq!(err.to_string(), "defaulted \"foo\"");
+    assert_eq!(DefaultedError::<u8, 1>::Array([1]).to_string(), "array [1]");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(assert_send_sync)]
+enum SendSyncError<T>
+where
+    T: std::fmt::Debug + Send + Sync + 'static,
+{
+    #[error_message("send sync")]
+    Value(T),
+}
+
+#[test]
+fn assert_send_sync_with_bounds() {
+    assert_eq!(SendSyncError::Value(1).to_string(), "send sync");
+}
+
+#[derive(ErrorStack, Debug)]
+enum PathError {
+    #[error_message(&format!("bad path {}", unnamed0.display()))]
+    Parse(std::path::PathBuf),
+    #[error_message(&format!("{} bytes in {}", bytes.len(), path.display()))]
+    Read {
+        path: std::path::PathBuf,
+        bytes: Vec<u8>,
+    },
+}
+
+#[test]
+fn messages_call_methods_on_borrowed_fields() {
+    let err = PathError::Parse(std::path::PathBuf::from("/tmp/foo"));
+    assert_eq!(err.to_string(), "bad path /tmp/foo");
+    // The fields are borrowed by the match arms, so formatting twice works
+    assert_eq!(err.to_string(), "bad path /tmp/foo");
+
+    let err = PathError::Read {
+        path: std::path::PathBuf::from("bar"),
+        bytes: vec![0; 3],
+    };
+    assert_eq!(err.to_string(), "3 bytes in bar");
+}
+
+#[derive(ErrorStack, Debug)]
+enum TransparentError {
+    #[error_message(transparent)]
+    Chain(#[from] ChainInnerError),
+    #[error_message(transparent)]
+    Parse { inner: std::num::ParseIntError },
+}
+
+#[test]
+fn transparent_forwards_display_and_source() {
+    use std::error::Error;
+
+    let io = std::io::Error::other("inner");
+    let err = TransparentError::from(ChainInnerError::from(io));
+    assert_eq!(err.to_string(), "middle");
+    assert_eq!(err.source().unwrap().to_string(), "inner");
+
+    let err = TransparentError::Parse {
+        inner: "a".parse::<u8>().unwrap_err(),
+    };
+    assert_eq!(err.to_string(), "invalid digit found in string");
+    assert!(err.source().is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+enum MixedParamsError<'a, T: std::fmt::Display + std::fmt::Debug> {
+    #[error_message("borrowed {0}")]
+    Borrowed(&'a str),
+    #[error_message(&format!("owned {}", unnamed0))]
+    Owned(T),
+    #[error_message("both {text} {value}")]
+    Both { text: &'a str, value: T },
+}
+
+#[test]
+fn mixed_lifetime_and_type_params() {
+    let text = String::from("foo");
+    assert_eq!(
+        MixedParamsError::<u8>::Borrowed(&text).to_string(),
+        "borrowed foo"
+    );
+    assert_eq!(MixedParamsError::<u8>::Owned(1).to_string(), "owned 1");
+    assert_eq!(
+        MixedParamsError::Both {
+            text: &text,
+            value: 2
+        }
+        .to_string(),
+        "both foo 2"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+enum CodedError<T: std::fmt::Debug> {
+    #[error_message("not found")]
+    #[error_code(1001)]
+    NotFound,
+    #[error_message("invalid {0:?}")]
+    #[error_code(1002)]
+    Invalid(T),
+}
+
+#[test]
+fn error_codes_convert_into_u32() {
+    let err = CodedError::Invalid(1);
+    assert_eq!(err.code(), 1002);
+
+    let code: u32 = (&err).into();
+    assert_eq!(code, 1002);
+    assert_eq!(u32::from(&CodedError::<u8>::NotFound), 1001);
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(template = "meta fallback")]
+enum MetaError {
+    #[error_message(message = "meta {0}", code = 1)]
+    Message(u8),
+    #[error_message(template = "meta template", code = 2)]
+    Template,
+    #[error_message(display = inner, code = 3)]
+    Display { inner: std::fmt::Error },
+    #[error_code(4)]
+    Fallback,
+}
+
+#[test]
+fn meta_list_messages_and_codes() {
+    assert_eq!(MetaError::Message(1).to_string(), "meta 1");
+    assert_eq!(MetaError::Message(1).code(), 1);
+    assert_eq!(MetaError::Template.to_string(), "meta template");
+    assert_eq!(MetaError::Template.code(), 2);
+
+    let err = MetaError::Display {
+        inner: std::fmt::Error,
+    };
+    assert_eq!(err.to_string(), std::fmt::Error.to_string());
+    assert_eq!(err.code(), 3);
+
+    assert_eq!(MetaError::Fallback.to_string(), "meta fallback");
+    assert_eq!(MetaError::Fallback.code(), 4);
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(max_len = 4, json)]
+enum TruncatedJsonError {
+    #[error_message("\"quoted\"")]
+    Quoted,
+}
+
+#[test]
+fn max_len_applies_before_json() {
+    assert_eq!(
+        TruncatedJsonError::Quoted.to_string(),
+        r#"{"error":"Quoted","message":"\"quo…"}"#
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("ordered fallback")]
+enum OrderedError {
+    First,
+    #[error_message("second")]
+    Second,
+    Third,
+    #[error_message("fourth")]
+    Fourth,
+}
+
+#[derive(ErrorStack, Debug)]
+enum OrderedDefaultError {
+    First,
+    #[error_message("second")]
+    Second,
+    Third,
+    #[error_message("fourth")]
+    Fourth(u8),
+}
+
+#[test]
+fn catch_all_arm_comes_last() {
+    assert_eq!(OrderedError::First.to_string(), "ordered fallback");
+    assert_eq!(OrderedError::Second.to_string(), "second");
+    assert_eq!(OrderedError::Third.to_string(), "ordered fallback");
+    assert_eq!(OrderedError::Fourth.to_string(), "fourth");
+
+    assert_eq!(
+        OrderedDefaultError::Third.to_string(),
+        "[OrderedDefaultError] An error occured; Third"
+    );
+    assert_eq!(OrderedDefaultError::Fourth(4).to_string(), "fourth");
+}
+
+#[derive(ErrorStack, Debug)]
+enum FlagsError {
+    #[error_message("delta {0:+}")]
+    Sign(i32),
+    #[error_message("padded {0:08}")]
+    Zero(i32),
+    #[error_message("right {0:>10}|")]
+    Align(i32),
+    #[error_message("named {value:^7}|")]
+    Named { value: i32 },
+}
+
+#[test]
+fn placeholder_flags_survive() {
+    assert_eq!(FlagsError::Sign(5).to_string(), "delta +5");
+    assert_eq!(FlagsError::Sign(-5).to_string(), "delta -5");
+    assert_eq!(FlagsError::Zero(-5).to_string(), "padded -0000005");
+    assert_eq!(FlagsError::Align(5).to_string(), "right          5|");
+    assert_eq!(FlagsError::Named { value: 5 }.to_string(), "named    5   |");
+}
+
+mod kinds {
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_stack(kind_enum = KindedErrorKind)]
+    pub enum KindedError<T: std::fmt::Debug> {
+        #[error_message("unit")]
+        Unit,
+        #[error_message("tuple {0:?}")]
+        Tuple(T),
+        #[error_message("named {value}")]
+        Named { value: u8 },
+    }
+}
+
+#[test]
+fn kind_enum_maps_variants() {
+    use kinds::{KindedError, KindedErrorKind};
+
+    let kinds = [
+        KindedError::<u8>::Unit.kind(),
+        KindedError::Tuple(1).kind(),
+        KindedError::<u8>::Named { value: 2 }.kind(),
+    ];
+    for kind in kinds {
+        let name = match kind {
+            KindedErrorKind::Unit => "Unit",
+            KindedErrorKind::Tuple => "Tuple",
+            KindedErrorKind::Named => "Named",
+        };
+        assert_eq!(format!("{kind:?}"), name);
+    }
+    assert_eq!(KindedError::Tuple(1).kind(), KindedErrorKind::Tuple);
+}
+
+#[derive(Debug)]
+struct Settings {
+    key: String,
+    limits: Limits,
+}
+
+#[derive(Debug)]
+struct Limits {
+    max: usize,
+}
+
+#[derive(ErrorStack, Debug)]
+enum NestedError {
+    #[error_message(&format!("bad key {}", settings.key))]
+    Config { settings: Settings },
+    #[error_message(&format!("over {} with {}", unnamed0.limits.max, unnamed0.key))]
+    Limit(Settings),
+}
+
+#[test]
+fn nested_field_access_in_variant_messages() {
+    let settings = || Settings {
+        key: String::from("foo"),
+        limits: Limits { max: 3 },
+    };
+
+    let err = NestedError::Config {
+        settings: settings(),
+    };
+    assert_eq!(err.to_string(), "bad key foo");
+    // Nothing was moved out of the error by formatting it
+    assert_eq!(err.to_string(), "bad key foo");
+
+    assert_eq!(
+        NestedError::Limit(settings()).to_string(),
+        "over 3 with foo"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(rename = "Validation")]
+enum ValidationErrorInternal {
+    Empty,
+    #[error_message("too long")]
+    TooLong,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(rename = "Validation", align_names = 6)]
+enum AlignedValidationErrorInternal {
+    Empty,
+}
+
+#[test]
+fn rename_replaces_the_name_in_the_default_message() {
+    assert_eq!(
+        ValidationErrorInternal::Empty.to_string(),
+        "[Validation] An error occured; Empty"
+    );
+    assert_eq!(ValidationErrorInternal::TooLong.to_string(), "too long");
+    assert_eq!(
+        AlignedValidationErrorInternal::Empty.to_string(),
+        "[Validation] Empty  An error occured; Empty"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(discriminant_code)]
+enum DiscriminantError {
+    #[error_message("a")]
+    A = 1,
+    #[error_message("b")]
+    B = 5,
+    #[error_message("c")]
+    C,
+    #[error_message("negative")]
+    Negative = -3,
+}
+
+#[test]
+fn discriminant_code_returns_the_discriminants() {
+    assert_eq!(DiscriminantError::A.code(), 1);
+    assert_eq!(DiscriminantError::B.code(), 5);
+    assert_eq!(DiscriminantError::C.code(), 6);
+    assert_eq!(DiscriminantError::Negative.code(), -3);
+
+    let code: i64 = (&DiscriminantError::B).into();
+    assert_eq!(code, 5);
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(discriminant_code)]
+enum EmptyDiscriminantError {}
+
+#[test]
+fn discriminant_code_of_an_empty_enum() {
+    // Uninhabited, it can't be constructed but its code compiles
+    let code: fn(&EmptyDiscriminantError) -> i64 = EmptyDiscriminantError::code;
+    let _ = code;
+}
+
+#[derive(ErrorStack, Debug)]
+#[repr(u16)]
+enum ReprError {
+    #[error_message("low")]
+    Low = 1,
+    High = 500,
+}
+
+#[derive(ErrorStack, Debug)]
+#[repr(u16)]
+#[error_stack(discriminant_code)]
+enum ReprCodeError {
+    #[error_message("low")]
+    Low = 1,
+    #[error_message("high")]
+    High = 500,
+}
+
+#[test]
+fn repr_coexists_with_the_derive() {
+    assert_eq!(ReprError::Low.to_string(), "low");
+    assert_eq!(
+        ReprError::High.to_string(),
+        "[ReprError] An error occured; High"
+    );
+
+    assert_eq!(ReprCodeError::Low.to_string(), "low");
+    let code: u16 = ReprCodeError::High.code();
+    assert_eq!(code, 500);
+    let code: u16 = (&ReprCodeError::Low).into();
+    assert_eq!(code, 1);
+}
+
+#[derive(ErrorStack)]
+#[error_stack(rich_debug)]
+enum RichDebugError {
+    #[error_message("bad input at line {line}")]
+    Parse { line: usize },
+    #[error_message("unit")]
+    Unit,
+}
+
+#[test]
+fn rich_debug_includes_the_message() {
+    assert_eq!(
+        format!("{:?}", RichDebugError::Parse { line: 3 }),
+        r#"Parse { display: "bad input at line 3" }"#
+    );
+    assert_eq!(
+        format!("{:?}", RichDebugError::Unit),
+        r#"Unit { display: "unit" }"#
+    );
+}
+
+#[derive(ErrorStack)]
+#[error_stack(rich_debug, fmt_with = Self::render)]
+enum RichDebugFmtWithError {
+    Parse { line: usize },
+    Unit,
+}
+
+impl RichDebugFmtWithError {
+    fn render(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse { line } => write!(fmt, "bad input at line {line}"),
+            Self::Unit => fmt.write_str("unit"),
+        }
+    }
+}
+
+#[test]
+fn rich_debug_includes_the_fmt_with_message() {
+    assert_eq!(
+        format!("{:?}", RichDebugFmtWithError::Parse { line: 3 }),
+        r#"Parse { display: "bad input at line 3" }"#
+    );
+    assert_eq!(
+        format!("{:?}", RichDebugFmtWithError::Unit),
+        r#"Unit { display: "unit" }"#
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("default {{escaped}}")]
+enum EscapedDefaultError {
+    Default,
+    #[error_message("variant {{escaped}} {0}")]
+    Variant(u8),
+}
+
+#[test]
+fn escaped_braces_are_unescaped() {
+    assert_eq!(
+        EscapedDefaultError::Default.to_string(),
+        "default {escaped}"
+    );
+    assert_eq!(
+        EscapedDefaultError::Variant(1).to_string(),
+        "variant {escaped} 1"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(constructors)]
+enum ConstructedError<T: std::fmt::Debug> {
+    #[error_message("not found")]
+    NotFound,
+    #[error_message(&format!("invalid {unnamed0} {unnamed1:?}"))]
+    Invalid(u8, T),
+    #[error_message(&format!("key {key}"))]
+    InvalidKey { key: String, line: usize },
+    #[error_message("io")]
+    IOError(#[source] std::io::Error),
+    #[error_message("type")]
+    Type,
+}
+
+#[test]
+fn constructors_build_the_variants() {
+    assert!(matches!(
+        ConstructedError::<u8>::not_found(),
+        ConstructedError::NotFound
+    ));
+    assert_eq!(
+        ConstructedError::invalid(1, "two").to_string(),
+        "invalid 1 \"two\""
+    );
+    let err = ConstructedError::<u8>::invalid_key("foo".into(), 3);
+    assert!(matches!(err, ConstructedError::InvalidKey { line: 3, .. }));
+    let io = ConstructedError::<u8>::io_error(std::io::ErrorKind::NotFound.into());
+    assert_eq!(io.to_string(), "io");
+    assert!(matches!(
+        ConstructedError::<u8>::r#type(),
+        ConstructedError::Type
+    ));
+}
+
+#[derive(ErrorStack, Debug)]
+enum CallbackError {
+    // Fields are bound by reference, which is callable just like the field
+    #[error_message((unnamed0)())]
+    Callback(fn() -> &'static str),
+    #[error_message(&format!("{} at {}", describe(), line))]
+    Named {
+        describe: fn() -> String,
+        line: usize,
+    },
+}
+
+#[test]
+fn callable_fields_are_invoked_in_messages() {
+    assert_eq!(CallbackError::Callback(|| "called").to_string(), "called");
+    let named = CallbackError::Named {
+        describe: || String::from("described"),
+        line: 4,
+    };
+    assert_eq!(named.to_string(), "described at 4");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(pad("default"))]
+enum PaddedError {
+    #[error_message(pad(&format!("padded {unnamed0}")))]
+    Padded(u8),
+    #[error_message("verbatim")]
+    Verbatim,
+    Default,
+}
+
+#[test]
+fn pad_honors_the_width() {
+    assert_eq!(
+        format!("{:>20}", PaddedError::Padded(1)),
+        "            padded 1"
+    );
+    assert_eq!(format!("{:>20}", PaddedError::Verbatim), "verbatim");
+    assert_eq!(format!("{:^9}", PaddedError::Default), " default ");
+}
+
+#[derive(ErrorStack, Debug)]
+enum TreeError {
+    #[error_message(&format!("wrap: {}", unnamed0))]
+    Wrap(Box<TreeError>),
+    #[error_message(&format!("pair: {left} & {right}"))]
+    Pair {
+        left: Box<TreeError>,
+        right: Box<TreeError>,
+    },
+    #[error_message("leaf")]
+    Leaf,
+    #[error_message(transparent)]
+    Forward(#[source] Box<TreeError>),
+}
+
+#[test]
+fn recursive_errors_format_their_children() {
+    let tree = TreeError::Wrap(Box::new(TreeError::Pair {
+        left: Box::new(TreeError::Leaf),
+        right: Box::new(TreeError::Wrap(Box::new(TreeError::Leaf))),
+    }));
+    assert_eq!(tree.to_string(), "wrap: pair: leaf & wrap: leaf");
+
+    let forward = TreeError::Forward(Box::new(TreeError::Wrap(Box::new(TreeError::Leaf))));
+    assert_eq!(forward.to_string(), "wrap: leaf");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(fallback_display)]
+enum FallbackDisplayError {
+    #[error_message("with message")]
+    Message(u8),
+    Unit,
+    Tuple(u8, std::net::Ipv4Addr),
+    Named {
+        r#type: &'static str,
+        inner: std::fmt::Error,
+    },
+}
+
+#[test]
+fn fallback_display_writes_the_fields() {
+    assert_eq!(FallbackDisplayError::Message(1).to_string(), "with message");
+    assert_eq!(FallbackDisplayError::Unit.to_string(), "Unit");
+    assert_eq!(
+        FallbackDisplayError::Tuple(1, [10, 0, 0, 1].into()).to_string(),
+        "Tuple(1, 10.0.0.1)"
+    );
+    let named = FallbackDisplayError::Named {
+        r#type: "int",
+        inner: std::fmt::Error,
+    };
+    assert_eq!(
+        named.to_string(),
+        "Named { type: int, inner: an error occurred when formatting an argument }"
+    );
+}
+
+mod deprecated_variants {
+    #![allow(deprecated)]
+
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message("default")]
+    pub enum LayeredError {
+        /// Documented before the message
+        #[deprecated(note = "use `Current`")]
+        #[cfg(not(any()))]
+        #[error_message("legacy")]
+        Legacy,
+        #[error_message(&format!("current {unnamed0}"))]
+        #[deprecated]
+        #[allow(unused)]
+        Current(u8),
+        #[deprecated]
+        #[doc(hidden)]
+        Plain,
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn messages_are_found_among_other_attributes() {
+    use deprecated_variants::LayeredError;
+
+    assert_eq!(LayeredError::Legacy.to_string(), "legacy");
+    assert_eq!(LayeredError::Current(1).to_string(), "current 1");
+    assert_eq!(LayeredError::Plain.to_string(), "default");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(prefix_with_code)]
+#[error_code(1)]
+enum CodePrefixedError {
+    #[error_message("not found")]
+    #[error_code(1001)]
+    NotFound,
+    #[error_message(&format!("invalid {unnamed0}"))]
+    Invalid(u8),
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(discriminant_code, prefix_with_code = "X")]
+enum DiscriminantPrefixedError {
+    #[error_message("usage")]
+    Usage = 64,
+}
+
+#[test]
+fn prefix_with_code_writes_the_code() {
+    assert_eq!(CodePrefixedError::NotFound.to_string(), "E1001: not found");
+    assert_eq!(CodePrefixedError::Invalid(2).to_string(), "E1: invalid 2");
+    assert_eq!(DiscriminantPrefixedError::Usage.to_string(), "X64: usage");
+}
+
+#[derive(ErrorStack, Debug)]
+enum CapacityError<T, const N: usize>
+where
+    T: std::fmt::Display + std::fmt::Debug,
+{
+    #[error_message(&format!("over by {} (cap {N})", unnamed0))]
+    Over(T),
+    #[error_message(&format!("empty (cap {N})"))]
+    Empty,
+}
+
+#[test]
+fn type_and_const_generics_in_variant_messages() {
+    assert_eq!(
+        CapacityError::<_, 8>::Over(3u8).to_string(),
+        "over by 3 (cap 8)"
+    );
+    assert_eq!(
+        CapacityError::<&str, 4>::Over("two").to_string(),
+        "over by two (cap 4)"
+    );
+    assert_eq!(CapacityError::<u8, 2>::Empty.to_string(), "empty (cap 2)");
+}
+
+#[deny(unused_variables)]
+mod wide {
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    pub enum WideError {
+        #[error_message(&format!("{unnamed0} {unnamed7} {unnamed10} {unnamed15}"))]
+        Wide(
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            &'static str,
+        ),
+        #[error_message("{1}{11}")]
+        Placeholders(
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+        ),
+    }
+}
+
+#[test]
+fn wide_tuple_variants_bind_every_field() {
+    use wide::WideError;
+
+    let wide = WideError::Wide(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, "last");
+    assert_eq!(wide.to_string(), "0 7 10 last");
+    let placeholders =
+        WideError::Placeholders(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+    assert_eq!(placeholders.to_string(), "111");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("unable to parse {}", self.0))]
+struct InnerParseError(String);
+
+#[derive(ErrorStack, Debug)]
+enum OuterConfigError {
+    #[error_message("invalid config")]
+    Parse(#[from] InnerParseError),
+    #[error_message("missing config")]
+    Missing,
+}
+
+fn parse_config(input: &str) -> Result<u8, OuterConfigError> {
+    Ok(input
+        .parse()
+        .map_err(|_| InnerParseError(input.to_owned()))?)
+}
+
+#[test]
+fn derived_errors_wrap_each_other() {
+    use std::error::Error;
+
+    let outer = parse_config("x").unwrap_err();
+    let inner = outer.source().unwrap();
+    assert_eq!(inner.to_string(), "unable to parse x");
+    assert!(inner.is::<InnerParseError>());
+    assert!(inner.source().is_none());
+
+    let report = error_stack::Report::new(InnerParseError("y".into()))
+        .change_context(OuterConfigError::Missing);
+    assert_eq!(report.to_string(), "missing config");
+    assert_eq!(format!("{report:#}"), "missing config: unable to parse y");
+    assert!(report.contains::<InnerParseError>());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(base = "Database error")]
+enum BasedError {
+    #[error_message("connection refused")]
+    Connection,
+    #[error_message(&format!("timed out after {unnamed0}s"))]
+    Timeout(u8),
+    Fallback,
+}
+
+#[test]
+fn base_prefixes_every_message() {
+    assert_eq!(
+        BasedError::Connection.to_string(),
+        "Database error: connection refused"
+    );
+    assert_eq!(
+        BasedError::Timeout(3).to_string(),
+        "Database error: timed out after 3s"
+    );
+    assert_eq!(
+        BasedError::Fallback.to_string(),
+        "Database error: [BasedError] An error occured; Fallback"
+    );
+}
+
+#[derive(ErrorStack)]
+enum LazyError<F: Fn() -> String> {
+    #[error_message(&(unnamed0)())]
+    Lazy(F),
+}
+
+// Closures aren't Debug, `#[derive(Debug)]` can't be used
+impl<F: Fn() -> String> std::fmt::Debug for LazyError<F> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.write_str("Lazy")
+    }
+}
+
+#[test]
+fn closure_fields_are_called_by_reference() {
+    let error = LazyError::Lazy(|| "computed lazily".to_owned());
+    assert_eq!(error.to_string(), "computed lazily");
+    assert_eq!(error.to_string(), "computed lazily");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(numbered)]
+enum FormError {
+    #[error_message(&unnamed0.join("\n"))]
+    Invalid(Vec<String>),
+    #[error_message("Empty form")]
+    Empty,
+}
+
+#[test]
+fn numbered_variant_lines_under_alternate() {
+    let err = FormError::Invalid(vec!["missing name".into(), "negative age".into()]);
+    assert_eq!(format!("{err}"), "missing name\nnegative age");
+    assert_eq!(format!("{err:#}"), "  1. missing name\n  2. negative age");
+    assert_eq!(format!("{:#}", FormError::Empty), "Empty form");
+}
+
+mod raw {
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_stack(json)]
+    pub enum r#Error {
+        #[error_message("x")]
+        r#Match,
+        r#Fallback,
+    }
+}
+
+#[test]
+fn raw_identifier_type_names_are_unraw() {
+    assert_eq!(
+        raw::Error::Match.to_string(),
+        r#"{"error":"Match","message":"x"}"#
+    );
+    assert_eq!(
+        raw::Error::Fallback.to_string(),
+        r#"{"error":"Fallback","message":"[Error] An error occured; Fallback"}"#
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(predicates, constructors)]
+enum LookupError {
+    #[error_message("Not found")]
+    NotFound,
+    #[error_message(&format!("Invalid key {key}"))]
+    InvalidKey { key: String },
+    #[error_message("IO")]
+    IOError(std::io::ErrorKind),
+    #[error_message("type")]
+    r#Type,
+}
+
+#[test]
+fn predicates_check_the_variant() {
+    let not_found = LookupError::not_found();
+    assert!(not_found.is_not_found());
+    assert!(!not_found.is_invalid_key());
+
+    let invalid = LookupError::invalid_key("port".into());
+    assert!(invalid.is_invalid_key());
+    assert!(!invalid.is_io_error());
+
+    assert!(LookupError::IOError(std::io::ErrorKind::Other).is_io_error());
+    assert!(LookupError::Type.is_type());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(predicates)]
+enum ModulePathError {
+    #[error_message("super")]
+    Super,
+    #[error_message("crate")]
+    Crate,
+}
+
+#[test]
+fn predicates_of_path_keywords() {
+    assert!(ModulePathError::Super.is_super());
+    assert!(!ModulePathError::Super.is_crate());
+    assert!(ModulePathError::Crate.is_crate());
+}
+
+#[derive(ErrorStack, Debug)]
+enum TableError {
+    #[error_message("{name:>width$}|{0:.1$}", name = "cell", width = 6)]
+    Cell(f32, usize, #[allow(dead_code)] u8),
+    #[error_message("{name:^width$}")]
+    Header { name: String, width: usize },
+}
+
+#[test]
+fn dollar_parameters_in_variant_messages() {
+    assert_eq!(TableError::Cell(1.5, 3, 0).to_string(), "  cell|1.500");
+    assert_eq!(
+        TableError::Header {
+            name: "id".into(),
+            width: 6
+        }
+        .to_string(),
+        "  id  "
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("Default")]
+#[error_stack(doc_messages, ensure_period)]
+enum DocumentedError {
+    /// The config file wasn't found
+    NotFound,
+    /// The key `{key}` isn't
+    /// a known key
+    ///
+    UnknownKey {
+        key: String,
+    },
+    /// Ignored in favor of the message
+    #[error_message("Explicit")]
+    Explicit(u8),
+    Undocumented,
+}
+
+#[test]
+fn doc_comments_are_variant_messages() {
+    assert_eq!(
+        DocumentedError::NotFound.to_string(),
+        "The config file wasn't found."
+    );
+    assert_eq!(
+        DocumentedError::UnknownKey { key: "port".into() }.to_string(),
+        "The key `{key}` isn't a known key."
+    );
+    assert_eq!(DocumentedError::Explicit(1).to_string(), "Explicit.");
+    assert_eq!(DocumentedError::Undocumented.to_string(), "Default.");
+}
@@ -0,0 +1,98 @@
+#![no_implicit_prelude]
+#![allow(dead_code)]
+
+use ::error_stack_derive::ErrorStack;
+use ::std::string::ToString;
+
+#[derive(ErrorStack, ::std::fmt::Debug)]
+#[error_message(::std::format!("struct {}", self.0))]
+#[error_stack(module_prefix)]
+struct StructError(u8);
+
+#[derive(ErrorStack, ::std::fmt::Debug)]
+enum EnumError {
+    #[error_message("unit")]
+    Unit,
+    #[error_message(debug)]
+    Debug(u8),
+    Io(#[from] ::std::io::Error),
+}
+
+#[derive(ErrorStack, ::std::fmt::Debug)]
+#[error_stack(write_fmt, align_names = 4)]
+enum FormatError {
+    #[error_message("format {unnamed0}")]
+    Format(u8),
+    Aligned,
+}
+
+#[derive(ErrorStack, ::std::fmt::Debug)]
+#[error_stack(json, display_chain)]
+enum JsonError {
+    #[error_message("json \"{0}\"\n")]
+    Json(u8),
+}
+
+#[derive(ErrorStack, ::std::fmt::Debug)]
+enum RelatedError {
+    #[error_message("many")]
+    Many(#[related] ::std::vec::Vec<::std::boxed::Box<dyn ::std::error::Error>>),
+    #[error_message("none")]
+    None,
+}
+
+#[derive(ErrorStack, ::std::fmt::Debug)]
+#[error_message("related")]
+struct RelatedStructError(#[related] ::std::vec::Vec<::std::boxed::Box<dyn ::std::error::Error>>);
+
+#[derive(ErrorStack, ::std::fmt::Debug)]
+#[error_message("truncated message")]
+#[error_stack(max_len = 9)]
+struct TruncatedError;
+
+#[derive(ErrorStack, ::std::fmt::Debug)]
+#[error_message("first\nsecond")]
+#[error_stack(numbered)]
+struct NumberedError;
+
+#[test]
+fn derives_without_prelude() {
+    use ::std::convert::From;
+    use ::std::error::Error;
+    use ::std::{assert, assert_eq};
+
+    assert_eq!(StructError(1).to_string(), "no_implicit_prelude: struct 1");
+    assert_eq!(EnumError::Unit.to_string(), "unit");
+    assert_eq!(EnumError::Debug(1).to_string(), "Debug(1)");
+
+    let io = ::std::io::Error::from(::std::io::ErrorKind::NotFound);
+    let err = EnumError::from(io);
+    assert!(err.source().is_some());
+
+    assert_eq!(FormatError::Format(2).to_string(), "format 2");
+    assert_eq!(
+        FormatError::Aligned.to_string(),
+        "[FormatError] Aligned An error occured; Aligned"
+    );
+
+    let err = RelatedError::Many(::std::vec![::std::boxed::Box::new(EnumError::Unit)]);
+    assert_eq!(::std::iter::Iterator::count(err.related()), 1);
+    assert_eq!(
+        ::std::iter::Iterator::count(RelatedError::None.related()),
+        0
+    );
+    let err = RelatedStructError(::std::vec![::std::boxed::Box::new(EnumError::Unit)]);
+    assert_eq!(::std::iter::Iterator::count(err.related()), 1);
+
+    assert_eq!(TruncatedError.to_string(), "truncated\u{2026}");
+
+    assert_eq!(
+        ::std::format!("{:#}", NumberedError),
+        "  1. first\n  2. second"
+    );
+
+    assert_eq!(
+        JsonError::Json(3).display_chain(),
+        "{\"error\":\"Json\",\"message\":\"json \\\"3\\\"\\n\"}"
+    );
+}
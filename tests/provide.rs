@@ -0,0 +1,93 @@
+#![cfg(feature = "provide")]
+#![feature(error_generic_member_access)]
+#![allow(dead_code)]
+
+use std::{
+    backtrace::Backtrace,
+    error::{request_ref, Error, Request},
+    fmt,
+};
+
+use error_stack_derive::ErrorStack;
+
+#[derive(Debug)]
+struct InnerError {
+    backtrace: Backtrace,
+}
+
+impl fmt::Display for InnerError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("inner")
+    }
+}
+
+impl Error for InnerError {
+    fn provide<'a>(&'a self, request: &mut Request<'a>) {
+        request.provide_ref::<Backtrace>(&self.backtrace);
+    }
+}
+
+#[derive(ErrorStack, Debug)]
+enum MiddleError {
+    #[error_message("middle")]
+    Inner(#[from] InnerError),
+}
+
+#[derive(ErrorStack, Debug)]
+enum OuterError {
+    #[error_message("outer")]
+    Middle {
+        #[source]
+        middle: MiddleError,
+    },
+}
+
+#[test]
+fn provide_is_forwarded_to_the_source() {
+    let inner = InnerError {
+        backtrace: Backtrace::force_capture(),
+    };
+    let err = OuterError::Middle {
+        middle: MiddleError::from(inner),
+    };
+
+    assert!(request_ref::<Backtrace>(&err).is_some());
+}
+
+#[derive(Debug)]
+struct RequestId(u64);
+
+#[derive(ErrorStack, Debug)]
+enum ProvidingError {
+    #[error_message("providing")]
+    Inner {
+        #[source]
+        inner: InnerError,
+        #[provide]
+        id: RequestId,
+    },
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("providing struct")]
+struct ProvidingStructError {
+    #[provide]
+    id: RequestId,
+}
+
+#[test]
+fn provided_fields_and_source_share_one_impl() {
+    let err = ProvidingError::Inner {
+        inner: InnerError {
+            backtrace: Backtrace::force_capture(),
+        },
+        id: RequestId(7),
+    };
+
+    assert_eq!(request_ref::<RequestId>(&err).unwrap().0, 7);
+    assert!(request_ref::<Backtrace>(&err).is_some());
+    assert!(err.source().is_some());
+
+    let err = ProvidingStructError { id: RequestId(8) };
+    assert_eq!(request_ref::<RequestId>(&err).unwrap().0, 8);
+}
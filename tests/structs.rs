@@ -0,0 +1,1169 @@
+#![allow(dead_code)]
+
+use error_stack_derive::ErrorStack;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("bounded: {}", self.0.as_ref()))]
+struct BoundedError<T: AsRef<str> + Clone + std::fmt::Debug>(T);
+
+#[test]
+fn inline_bounds_are_preserved() {
+    assert_eq!(BoundedError("foo").to_string(), "bounded: foo");
+    assert_eq!(
+        BoundedError(String::from("bar")).to_string(),
+        "bounded: bar"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("borrowed")]
+struct BorrowedMessageError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(format!("owned {}", self.0))]
+struct OwnedMessageError(u8);
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("borrowed owned {}", self.0))]
+struct BorrowedOwnedMessageError(u8);
+
+#[test]
+fn messages_can_be_str_or_string() {
+    assert_eq!(BorrowedMessageError.to_string(), "borrowed");
+    assert_eq!(OwnedMessageError(1).to_string(), "owned 1");
+    assert_eq!(BorrowedOwnedMessageError(2).to_string(), "borrowed owned 2");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("boxed source")]
+#[error_stack(source_fn = my_source)]
+struct SourceFnError {
+    inner: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl SourceFnError {
+    fn my_source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.inner)
+    }
+}
+
+#[test]
+fn source_fn_is_used_as_source() {
+    use std::error::Error;
+
+    let err = SourceFnError {
+        inner: Box::new(BorrowedMessageError),
+    };
+    let source = err.source().expect("source");
+    assert_eq!(source.to_string(), "borrowed");
+    assert!(source.is::<BorrowedMessageError>());
+}
+
+mod prefixed {
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message("connection refused")]
+    #[error_stack(module_prefix)]
+    pub struct ModulePrefixError;
+}
+
+#[test]
+fn module_prefix_prepends_module_path() {
+    assert_eq!(
+        prefixed::ModulePrefixError.to_string(),
+        "structs::prefixed: connection refused"
+    );
+    assert_eq!(BorrowedMessageError.to_string(), "borrowed");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("{}", self.val))]
+struct LifetimeBoundError<'a, T>
+where
+    T: 'a + std::fmt::Display + std::fmt::Debug,
+{
+    val: &'a T,
+}
+
+#[test]
+fn where_clause_lifetime_bounds_are_preserved() {
+    let val = String::from("borrowed value");
+    assert_eq!(
+        LifetimeBoundError { val: &val }.to_string(),
+        "borrowed value"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("{}-{:>4}", self.0, self.1)]
+#[error_stack(write_fmt)]
+struct WriteFmtError(u8, &'static str);
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("{}-{:>4}", self.0, self.1))]
+struct AllocatingError(u8, &'static str);
+
+#[test]
+fn write_fmt_matches_allocating_message() {
+    assert_eq!(WriteFmtError(1, "a").to_string(), "1-   a");
+    assert_eq!(
+        WriteFmtError(1, "a").to_string(),
+        AllocatingError(1, "a").to_string()
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(concat!(env!("CARGO_PKG_NAME"), " v", env!("CARGO_PKG_VERSION"), ": failure"))]
+struct BuildConstantError;
+
+#[test]
+fn macro_call_messages_are_expanded() {
+    assert_eq!(
+        BuildConstantError.to_string(),
+        format!("error-stack-derive v{}: failure", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(fmt_with = render_nested)]
+struct FmtWithError {
+    inner: BoundedError<&'static str>,
+}
+
+fn render_nested(err: &FmtWithError, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fmt.write_str("outer (")?;
+    std::fmt::Display::fmt(&err.inner, fmt)?;
+    fmt.write_str(")")
+}
+
+#[test]
+fn fmt_with_renders_through_the_formatter() {
+    let err = FmtWithError {
+        inner: BoundedError("inner"),
+    };
+    assert_eq!(err.to_string(), "outer (bounded: inner)");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(debug)]
+struct DebugUnitError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(debug)]
+struct DebugNamedError {
+    line: usize,
+    reason: &'static str,
+}
+
+#[test]
+fn debug_message_writes_debug_representation() {
+    assert_eq!(DebugUnitError.to_string(), format!("{DebugUnitError:?}"));
+    assert_eq!(DebugUnitError.to_string(), "DebugUnitError");
+
+    let named = DebugNamedError {
+        line: 1,
+        reason: "eof",
+    };
+    assert_eq!(named.to_string(), format!("{named:?}"));
+    assert_eq!(
+        named.to_string(),
+        r#"DebugNamedError { line: 1, reason: "eof" }"#
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("{}-{}", self.0, self.1))]
+struct PairError(u8, u8);
+
+#[test]
+fn tuple_fields_are_accessible_through_self() {
+    assert_eq!(PairError(1, 2).to_string(), "1-2");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!(
+    "a={}, b={}",
+    self.a,
+    self.b,
+))]
+struct TrailingCommaError {
+    a: u8,
+    b: u8,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("a={}, b={}", self.a, self.b,)]
+#[error_stack(write_fmt)]
+struct TrailingCommaWriteFmtError {
+    a: u8,
+    b: u8,
+}
+
+#[test]
+fn trailing_commas_in_format_arguments() {
+    assert_eq!(TrailingCommaError { a: 1, b: 2 }.to_string(), "a=1, b=2");
+    assert_eq!(
+        TrailingCommaWriteFmtError { a: 1, b: 2 }.to_string(),
+        "a=1, b=2"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("{} errors", self.errors.len()))]
+struct AggregateError {
+    #[related]
+    errors: Vec<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+#[test]
+fn related_iterates_over_aggregated_errors() {
+    let err = AggregateError {
+        errors: vec![Box::new(PairError(1, 2)), Box::new(BorrowedMessageError)],
+    };
+    assert_eq!(err.to_string(), "2 errors");
+    let related = err.related().map(|err| err.to_string()).collect::<Vec<_>>();
+    assert_eq!(related, ["1-2", "borrowed"]);
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("limit is {N}"))]
+struct LimitError<const N: usize>;
+
+#[test]
+fn const_params_are_in_scope_of_the_message() {
+    assert_eq!(LimitError::<16>.to_string(), "limit is 16");
+    assert_eq!(LimitError::<0>.to_string(), "limit is 0");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("tuple: {}", self.0))]
+#[error_stack(from_string)]
+struct FromStringError(String);
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("named: {}", self.message))]
+#[error_stack(from_string)]
+struct FromStringNamedError {
+    message: std::string::String,
+}
+
+#[test]
+fn from_string_converts_str_and_string() {
+    let from_str: FromStringError = "a".into();
+    assert_eq!(from_str.to_string(), "tuple: a");
+    let from_string: FromStringError = String::from("b").into();
+    assert_eq!(from_string.to_string(), "tuple: b");
+
+    let from_str = FromStringNamedError::from("c");
+    assert_eq!(from_str.to_string(), "named: c");
+    let from_string = FromStringNamedError::from(String::from("d"));
+    assert_eq!(from_string.to_string(), "named: d");
+
+    let mapped = "x"
+        .parse::<u8>()
+        .map_err(|e| FromStringError::from(e.to_string()));
+    assert!(mapped.is_err());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(display = 1)]
+struct DisplayTupleError(u8, &'static str);
+
+#[derive(ErrorStack, Debug)]
+#[error_message(display = inner)]
+struct DisplayNamedError {
+    inner: PairError,
+}
+
+#[test]
+fn display_forwards_to_the_field() {
+    assert_eq!(DisplayTupleError(0, "second").to_string(), "second");
+    assert_eq!(format!("{:>8}", DisplayTupleError(0, "flag")), "    flag");
+    assert_eq!(
+        DisplayNamedError {
+            inner: PairError(1, 2)
+        }
+        .to_string(),
+        "1-2"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(display_expr = self.path())]
+struct DisplayExprError(std::path::PathBuf);
+
+impl DisplayExprError {
+    fn path(&self) -> std::path::Display<'_> {
+        self.0.display()
+    }
+}
+
+#[test]
+fn display_expr_forwards_to_the_expression() {
+    let err = DisplayExprError("foo/bar".into());
+    assert_eq!(err.to_string(), "foo/bar");
+    assert_eq!(format!("{err:>9}"), "  foo/bar");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("explicit")]
+#[error_stack(explicit_source)]
+struct ExplicitSourceError;
+
+#[test]
+fn explicit_source_returns_none() {
+    use std::error::Error;
+
+    assert!(ExplicitSourceError.source().is_none());
+}
+
+trait Failure: std::fmt::Debug {}
+
+impl Failure for u8 {}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("boxed failure")]
+struct BoxedTraitError(Box<dyn Failure + Send + Sync>);
+
+#[derive(ErrorStack, Debug)]
+#[error_message("complex fields")]
+struct ComplexFieldsError {
+    callback: fn(&str) -> Result<(), Box<dyn std::error::Error>>,
+    slice: &'static [(u8, [u16; 4])],
+    map: std::collections::HashMap<String, Vec<Option<Box<dyn Failure + Send>>>>,
+}
+
+#[test]
+fn trait_object_fields_derive() {
+    assert_eq!(BoxedTraitError(Box::new(3)).to_string(), "boxed failure");
+
+    let err = ComplexFieldsError {
+        callback: |_| Ok(()),
+        slice: &[],
+        map: Default::default(),
+    };
+    assert_eq!(err.to_string(), "complex fields");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("struct \"{0}\"")]
+#[error_stack(json)]
+struct JsonStructError(u8);
+
+#[test]
+fn json_display_of_struct() {
+    assert_eq!(
+        JsonStructError(1).to_string(),
+        r#"{"error":"JsonStructError","message":"struct \"1\""}"#
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("defaulted {0}")]
+struct DefaultedError<T: std::fmt::Display + std::fmt::Debug = String>(T);
+
+#[test]
+fn default_type_parameters() {
+    let err: DefaultedError = DefaultedError(String::from("foo"));
+    assert_eq!(err.to_string(), "defaulted foo");
+    assert_eq!(DefaultedError(1).to_string(), "defaulted 1");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(transparent)]
+struct TransparentError(std::fmt::Error);
+
+#[test]
+fn transparent_struct_forwards_display() {
+    use std::error::Error;
+
+    assert_eq!(
+        TransparentError(std::fmt::Error).to_string(),
+        std::fmt::Error.to_string()
+    );
+    assert!(TransparentError(std::fmt::Error).source().is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("type: {}", self.r#type))]
+struct RawFieldError {
+    r#type: String,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("type: {type}, match: {r#match}")]
+struct RawPlaceholderError {
+    r#type: String,
+    r#match: u8,
+}
+
+#[test]
+fn raw_identifier_fields() {
+    let err = RawFieldError {
+        r#type: String::from("foo"),
+    };
+    assert_eq!(err.to_string(), "type: foo");
+
+    let err = RawPlaceholderError {
+        r#type: String::from("bar"),
+        r#match: 1,
+    };
+    assert_eq!(err.to_string(), "type: bar, match: 1");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("part one, ", "part two {0}, ", "part three")]
+#[error_stack(ensure_period)]
+struct SplitMessageError(u8);
+
+#[test]
+fn split_messages_are_concatenated() {
+    assert_eq!(
+        SplitMessageError(2).to_string(),
+        "part one, part two 2, part three."
+    );
+}
+
+static EVALUATIONS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+fn counted(message: &str) -> &str {
+    EVALUATIONS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    message
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(counted("lazy"))]
+struct LazyError;
+
+#[test]
+fn messages_are_evaluated_when_formatted() {
+    use std::sync::atomic::Ordering;
+
+    let err = LazyError;
+    assert_eq!(EVALUATIONS.load(Ordering::SeqCst), 0);
+
+    assert_eq!(err.to_string(), "lazy");
+    assert_eq!(EVALUATIONS.load(Ordering::SeqCst), 1);
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("coded")]
+#[error_code(42)]
+struct CodedError;
+
+#[test]
+fn error_code_converts_into_u32() {
+    assert_eq!(CodedError.code(), 42);
+    let code: u32 = (&CodedError).into();
+    assert_eq!(code, 42);
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(message = "meta {0}", code = 7)]
+#[error_stack(ensure_period)]
+struct MetaError(u8);
+
+#[test]
+fn meta_list_message_and_code() {
+    assert_eq!(MetaError(1).to_string(), "meta 1.");
+    assert_eq!(MetaError(1).code(), 7);
+}
+
+macro_rules! define_error {
+    ($name:ident, $field:ident, $message:literal) => {
+        #[derive(ErrorStack, Debug)]
+        #[error_message(&format!($message, self.$field))]
+        struct $name {
+            $field: u8,
+        }
+    };
+}
+
+define_error!(MacroError, fmt, "macro {}");
+define_error!(MacroSelfError, value, "macro self {}");
+
+#[test]
+fn derives_inside_macro_rules() {
+    assert_eq!(MacroError { fmt: 1 }.to_string(), "macro 1");
+    assert_eq!(MacroSelfError { value: 2 }.to_string(), "macro self 2");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&self.0)]
+#[error_stack(max_len = 5)]
+struct TruncatedError(String);
+
+#[test]
+fn max_len_truncates_on_char_boundaries() {
+    let truncated = |message: &str| TruncatedError(message.to_owned()).to_string();
+
+    assert_eq!(truncated("short"), "short");
+    assert_eq!(truncated("abc"), "abc");
+    assert_eq!(truncated("longer message"), "longe…");
+    // Multi-byte characters are counted as one
+    assert_eq!(truncated("ééééé"), "ééééé");
+    assert_eq!(truncated("日本語のメッセージ"), "日本語のメ…");
+}
+
+const fn compute_message() -> &'static str {
+    "computed"
+}
+
+const fn select_message(verbose: bool) -> &'static str {
+    match verbose {
+        true => "computed verbosely",
+        false => "computed",
+    }
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(compute_message())]
+struct ConstFnError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(select_message(self.0))]
+struct ConstFnArgsError(bool);
+
+#[test]
+fn const_fn_messages() {
+    assert_eq!(ConstFnError.to_string(), "computed");
+    assert_eq!(ConstFnArgsError(true).to_string(), "computed verbosely");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("delta {0:+} padded {0:08} right {0:>10} fill {0:*<6} hex {0:#06x}")]
+struct FlagsError(i32);
+
+#[test]
+fn placeholder_flags_survive() {
+    assert_eq!(
+        FlagsError(42).to_string(),
+        "delta +42 padded 00000042 right         42 fill 42**** hex 0x002a"
+    );
+}
+
+// Both configurations of a cfg'd field are covered, the field is enabled
+// in one struct and disabled in the other
+#[derive(ErrorStack, Debug)]
+#[cfg_attr(test, error_message(&format!("enabled {}", self.maybe)))]
+#[cfg_attr(not(test), error_message("disabled"))]
+struct CfgEnabledFieldError {
+    #[cfg(test)]
+    maybe: u8,
+}
+
+#[derive(ErrorStack, Debug)]
+#[cfg_attr(not(test), error_message(&format!("enabled {}", self.maybe)))]
+#[cfg_attr(test, error_message("disabled"))]
+struct CfgDisabledFieldError {
+    #[cfg(not(test))]
+    maybe: u8,
+}
+
+#[test]
+fn cfg_fields_in_messages() {
+    assert_eq!(CfgEnabledFieldError { maybe: 1 }.to_string(), "enabled 1");
+    assert_eq!(CfgDisabledFieldError {}.to_string(), "disabled");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("renamed")]
+#[error_stack(json, rename = "Renamed")]
+struct RenamedJsonErrorInternal;
+
+#[derive(ErrorStack, Debug)]
+#[error_message("a")]
+#[error_stack(json, rename = "Bad\"Name\\")]
+struct QuotedJsonError;
+
+#[test]
+fn rename_replaces_the_json_name() {
+    assert_eq!(
+        RenamedJsonErrorInternal.to_string(),
+        r#"{"error":"Renamed","message":"renamed"}"#
+    );
+    assert_eq!(
+        QuotedJsonError.to_string(),
+        r#"{"error":"Bad\"Name\\","message":"a"}"#
+    );
+}
+
+mod render {
+    pub fn type_name<T>() -> &'static str {
+        std::any::type_name::<T>().rsplit("::").next().unwrap()
+    }
+
+    pub fn join<T: std::fmt::Display, const N: usize>(values: [T; N]) -> String {
+        values.map(|value| value.to_string()).join(", ")
+    }
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(render::type_name::<Self>())]
+struct TurbofishError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(render::join::<u8, 2>([self.0, self.1]))]
+struct GenericCallError(u8, u8);
+
+#[test]
+fn turbofish_in_messages() {
+    assert_eq!(TurbofishError.to_string(), "TurbofishError");
+    assert_eq!(GenericCallError(1, 2).to_string(), "1, 2");
+}
+
+#[derive(ErrorStack)]
+#[error_message("rich {0}")]
+#[error_stack(rich_debug)]
+struct RichDebugError(u8);
+
+#[test]
+fn rich_debug_includes_the_message() {
+    assert_eq!(
+        format!("{:?}", RichDebugError(1)),
+        r#"RichDebugError { display: "rich 1" }"#
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("checksum len {N}"))]
+struct ChecksumError<const N: usize>([u8; N]);
+
+#[derive(ErrorStack, Debug)]
+#[error_message("checksum {0:?}")]
+struct ChecksumPlaceholderError<const N: usize>([u8; N]);
+
+#[test]
+fn const_generic_array_fields() {
+    assert_eq!(ChecksumError([0; 32]).to_string(), "checksum len 32");
+    assert_eq!(
+        ChecksumPlaceholderError([1, 2]).to_string(),
+        "checksum [1, 2]"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("timed out after {:?}", self.elapsed))]
+struct TimeoutError {
+    elapsed: std::time::Duration,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("unable to read {}", self.0.display()))]
+struct ReadError(std::path::PathBuf);
+
+#[derive(ErrorStack, Debug)]
+#[error_message("unable to connect to {addr}")]
+struct ConnectError {
+    addr: std::net::SocketAddr,
+}
+
+#[test]
+fn std_field_types() {
+    let err = TimeoutError {
+        elapsed: std::time::Duration::from_millis(1500),
+    };
+    assert_eq!(err.to_string(), "timed out after 1.5s");
+    assert_eq!(
+        ReadError("foo/bar.txt".into()).to_string(),
+        "unable to read foo/bar.txt"
+    );
+    let err = ConnectError {
+        addr: ([127, 0, 0, 1], 8080).into(),
+    };
+    assert_eq!(err.to_string(), "unable to connect to 127.0.0.1:8080");
+}
+
+error_stack_derive::errors! {
+    ListedError => "A listed error occured",
+    /// Documented errors keep their docs
+    pub(crate) OtherListedError => "Another listed error occured",
+    #[error_stack(ensure_period)]
+    #[error_code(7)]
+    LastListedError => "The last listed error occured",
+}
+
+#[test]
+fn declares_errors_from_a_list() {
+    assert_eq!(ListedError.to_string(), "A listed error occured");
+    assert_eq!(OtherListedError.to_string(), "Another listed error occured");
+    assert_eq!(
+        LastListedError.to_string(),
+        "The last listed error occured."
+    );
+    assert_eq!(LastListedError.code(), 7);
+    assert_eq!(format!("{ListedError:?}"), "ListedError");
+    assert!(std::error::Error::source(&OtherListedError).is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("gated")]
+#[error_stack(error_cfg = "any(test, feature = \"provide\")")]
+struct GatedError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message("display only")]
+#[error_stack(error_cfg = "not(test)")]
+struct DisplayOnlyError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message("gated chain")]
+#[error_stack(error_cfg = "not(test)", display_chain)]
+struct GatedChainError<A: std::fmt::Debug>(A);
+
+#[derive(ErrorStack, Debug)]
+#[error_message("gated separator")]
+#[error_stack(error_cfg = "not(test)", chain_sep = " -> ")]
+struct GatedSeparatorError(#[source] std::fmt::Error);
+
+#[derive(ErrorStack, Debug)]
+#[error_message("enabled chain")]
+#[error_stack(error_cfg = "test", chain_sep = " -> ")]
+struct EnabledChainError(#[source] std::fmt::Error);
+
+#[test]
+fn error_impl_is_gated_by_error_cfg() {
+    let err: &dyn std::error::Error = &GatedError;
+    assert_eq!(err.to_string(), "gated");
+    assert_eq!(DisplayOnlyError.to_string(), "display only");
+}
+
+#[test]
+fn display_chain_is_gated_by_error_cfg() {
+    // Without the Error impl there's no display_chain either
+    assert_eq!(GatedChainError(1).to_string(), "gated chain");
+    assert_eq!(
+        GatedSeparatorError(std::fmt::Error).to_string(),
+        "gated separator"
+    );
+    assert_eq!(
+        EnabledChainError(std::fmt::Error).display_chain(),
+        "enabled chain -> an error occurred when formatting an argument"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(pad("padded"))]
+struct PaddedError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message("verbatim")]
+struct VerbatimError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(pad("padded {0}"))]
+#[error_stack(ensure_period)]
+struct PaddedPlaceholderError(u8);
+
+#[test]
+fn pad_honors_the_width() {
+    assert_eq!(format!("{:>20}", PaddedError), "              padded");
+    assert_eq!(format!("{:>20}", VerbatimError), "verbatim");
+    assert_eq!(
+        format!("{:<12}|", PaddedPlaceholderError(1)),
+        "padded 1.   |"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_stack(no_display_impl)]
+struct HandWrittenError {
+    code: u8,
+    #[source]
+    inner: std::fmt::Error,
+}
+
+impl std::fmt::Display for HandWrittenError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "hand written {}", self.code)
+    }
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("tuple source")]
+struct TupleSourceError(u8, #[source] std::io::Error);
+
+#[test]
+fn no_display_impl_keeps_the_hand_written_display() {
+    use std::error::Error;
+
+    let err = HandWrittenError {
+        code: 3,
+        inner: std::fmt::Error,
+    };
+    assert_eq!(err.to_string(), "hand written 3");
+    assert!(err.source().unwrap().is::<std::fmt::Error>());
+
+    let err = TupleSourceError(0, std::io::ErrorKind::NotFound.into());
+    assert!(err.source().unwrap().is::<std::io::Error>());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("wrapped")]
+struct WrappedError<C>(#[from] C)
+where
+    C: std::error::Error + Send + Sync + 'static;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("at line {}", self.line))]
+struct LineError {
+    #[from(map = |source| Self { source, line: 0 })]
+    source: std::num::ParseIntError,
+    line: usize,
+}
+
+#[test]
+fn from_field_converts_into_the_struct() {
+    use std::error::Error;
+
+    let err = WrappedError::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+    assert!(err.source().unwrap().is::<std::io::Error>());
+    let err: WrappedError<std::fmt::Error> = std::fmt::Error.into();
+    assert_eq!(err.to_string(), "wrapped");
+
+    let err = LineError::from("x".parse::<u8>().unwrap_err());
+    assert_eq!(err.to_string(), "at line 0");
+    assert!(err.source().is_some());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("not found")]
+#[error_code(1001)]
+#[error_stack(prefix_with_code)]
+struct CodePrefixedError;
+
+#[derive(ErrorStack, Debug)]
+#[error_message(message = "forbidden", code = 403)]
+#[error_stack(prefix_with_code = "HTTP ")]
+struct HttpPrefixedError;
+
+#[test]
+fn prefix_with_code_writes_the_code() {
+    assert_eq!(CodePrefixedError.to_string(), "E1001: not found");
+    assert_eq!(HttpPrefixedError.to_string(), "HTTP 403: forbidden");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("first: {}", self.0[0]))]
+struct FirstError(Vec<String>);
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("head: {:?}, rest: {}", &self.names[..1], self.names[1..].len()))]
+struct SlicedError {
+    names: Vec<String>,
+}
+
+#[test]
+fn messages_index_and_slice_fields() {
+    let err = FirstError(vec!["a".into(), "b".into()]);
+    assert_eq!(err.to_string(), "first: a");
+    // Formatting borrows the field, it's still there afterwards
+    assert_eq!(err.0.len(), 2);
+
+    let err = SlicedError {
+        names: vec!["a".into(), "b".into(), "c".into()],
+    };
+    assert_eq!(err.to_string(), "head: [\"a\"], rest: 2");
+}
+
+#[deny(unused_parens)]
+mod clean_parens {
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message("literal")]
+    pub struct LiteralError;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message(self.0.clone() + " appended")]
+    pub struct BinaryError(pub String);
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message(format!("{}", self.0).as_str())]
+    pub struct TemporaryError(pub u8);
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message(pad(self.0.clone() + "!"))]
+    pub struct PaddedBinaryError(pub String);
+
+    pub struct Msg {
+        pub s: &'static str,
+    }
+
+    impl AsRef<str> for Msg {
+        fn as_ref(&self) -> &str {
+            self.s
+        }
+    }
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message(Msg { s: "x" })]
+    pub struct StructLiteralError;
+}
+
+#[test]
+fn messages_need_no_parentheses() {
+    use clean_parens::*;
+
+    assert_eq!(LiteralError.to_string(), "literal");
+    assert_eq!(BinaryError("a".into()).to_string(), "a appended");
+    assert_eq!(TemporaryError(3).to_string(), "3");
+    assert_eq!(format!("{:>3}", PaddedBinaryError("a".into())), " a!");
+    assert_eq!(StructLiteralError.to_string(), "x");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("invalid {key} at {line}"))]
+#[error_stack(bind_fields)]
+struct BoundFieldsError {
+    key: &'static str,
+    line: usize,
+    r#type: u8,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("tuple {unnamed1}"))]
+#[error_stack(bind_fields)]
+struct BoundTupleError(u8, &'static str);
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("via self {}", self.line))]
+struct SelfFieldsError {
+    line: usize,
+}
+
+#[test]
+fn bind_fields_captures_struct_fields() {
+    let err = BoundFieldsError {
+        key: "port",
+        line: 3,
+        r#type: 0,
+    };
+    assert_eq!(err.to_string(), "invalid port at 3");
+    assert_eq!(BoundTupleError(0, "second").to_string(), "tuple second");
+    assert_eq!(SelfFieldsError { line: 1 }.to_string(), "via self 1");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&self.msg)]
+struct ReferencedMessageError {
+    msg: String,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(self.msg.as_str())]
+struct StrMessageError {
+    msg: String,
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(self.0)]
+struct StaticMessageError(&'static str);
+
+#[test]
+fn messages_referencing_string_fields() {
+    let err = ReferencedMessageError { msg: "ref".into() };
+    assert_eq!(err.to_string(), "ref");
+    let err = StrMessageError { msg: "str".into() };
+    assert_eq!(err.to_string(), "str");
+    assert_eq!(StaticMessageError("static").to_string(), "static");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("connection refused")]
+#[error_stack(base = "Database error")]
+struct BasedError;
+
+#[test]
+fn base_prefixes_the_message() {
+    assert_eq!(BasedError.to_string(), "Database error: connection refused");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&self.0.join("\n"))]
+#[error_stack(numbered)]
+struct ValidationError(Vec<&'static str>);
+
+#[test]
+fn numbered_lines_under_alternate() {
+    let single = ValidationError(vec!["missing name"]);
+    assert_eq!(format!("{single}"), "missing name");
+    assert_eq!(format!("{single:#}"), "missing name");
+
+    let multi = ValidationError(vec!["missing name", "negative age", "bad email"]);
+    assert_eq!(format!("{multi}"), "missing name\nnegative age\nbad email");
+    assert_eq!(
+        format!("{multi:#}"),
+        "  1. missing name\n  2. negative age\n  3. bad email"
+    );
+}
+
+mod raw {
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message("x")]
+    #[error_stack(json)]
+    pub struct r#Error;
+}
+
+#[test]
+fn raw_identifier_type_names_are_unraw() {
+    assert_eq!(raw::Error.to_string(), r#"{"error":"Error","message":"x"}"#);
+}
+
+mod messages {
+    pub const NOT_FOUND: &str = "Not found";
+
+    pub fn timeout(secs: u64) -> String {
+        format!("Timed out after {secs}s")
+    }
+}
+
+mod imported {
+    use crate::messages::{timeout, NOT_FOUND};
+    use error_stack_derive::ErrorStack;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message(NOT_FOUND)]
+    pub struct NotFoundError;
+
+    #[derive(ErrorStack, Debug)]
+    #[error_message(&timeout(self.0))]
+    #[error_stack(assert_send_sync)]
+    pub struct TimeoutError(pub u64);
+}
+
+#[test]
+fn messages_reference_imported_items() {
+    assert_eq!(imported::NotFoundError.to_string(), "Not found");
+    assert_eq!(imported::TimeoutError(3).to_string(), "Timed out after 3s");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("Invalid value {}", self.0))]
+#[error_stack(assert_send_sync, bind_fields)]
+// Every bound the impls need, `T = str` checks `?Sized` is kept
+struct UnsizedError<T: ?Sized + std::fmt::Display + std::fmt::Debug + Send + Sync + 'static>(
+    Box<T>,
+);
+
+#[test]
+fn maybe_unsized_type_parameters_keep_their_bound() {
+    let err = UnsizedError::<str>(Box::from("port"));
+    assert_eq!(err.to_string(), "Invalid value port");
+    let err: &dyn std::error::Error = &err;
+    assert!(err.source().is_none());
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(
+    "[{0:>1$}] [{value:.precision$}]",
+    value = self.2,
+    precision = self.precision(),
+)]
+struct AlignedError(&'static str, usize, f64, #[allow(dead_code)] usize);
+
+impl AlignedError {
+    fn precision(&self) -> usize {
+        self.3
+    }
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message("[{name:width$}]")]
+struct WidthError {
+    name: &'static str,
+    width: usize,
+}
+
+#[test]
+fn dollar_parameters_are_fields_or_arguments() {
+    assert_eq!(
+        WidthError {
+            name: "id",
+            width: 4
+        }
+        .to_string(),
+        "[id  ]"
+    );
+    assert_eq!(
+        AlignedError("id", 4, 1.23456, 2).to_string(),
+        "[  id] [1.23]"
+    );
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("Invalid input {}", self.0))]
+#[error_stack(escape_control)]
+struct ControlError(&'static str);
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("Invalid input {}", self.0))]
+#[error_stack(escape_whitespace)]
+struct WhitespaceError(&'static str);
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&format!("Invalid {}", self.0))]
+#[error_stack(escape_control, max_len = 13)]
+struct TruncatedControlError(&'static str);
+
+#[test]
+fn control_characters_are_escaped() {
+    assert_eq!(
+        ControlError("bell\x07\r\nnext\tline").to_string(),
+        "Invalid input bell\\u{7}\\r\nnext\tline"
+    );
+    assert_eq!(
+        WhitespaceError("bell\x07\r\nnext\tline").to_string(),
+        "Invalid input bell\\u{7}\\r\\nnext\\tline"
+    );
+    assert_eq!(
+        ControlError("plain ünïcode").to_string(),
+        "Invalid input plain ünïcode"
+    );
+}
+
+#[test]
+fn escaped_messages_are_truncated_to_max_len() {
+    assert_eq!(
+        TruncatedControlError("\x07\x07\x07").to_string(),
+        "Invalid \\u{7}\u{2026}"
+    );
+    assert_eq!(TruncatedControlError("\x07").to_string(), "Invalid \\u{7}");
+}
+
+#[derive(ErrorStack, Debug)]
+#[error_message(&match &self.parent {
+    Some(parent) => format!("{} in {parent}", self.name),
+    None => self.name.to_owned(),
+})]
+#[error_stack(assert_send_sync)]
+struct NodeError {
+    name: &'static str,
+    parent: Option<Box<Self>>,
+}
+
+// Only the bounds the message and `Debug` need
+#[derive(ErrorStack, Debug)]
+#[error_message("{name:?} ({0} children)", name = self.1)]
+struct GenericNodeError<T: std::fmt::Debug>(usize, T, Vec<GenericNodeError<T>>);
+
+#[test]
+fn recursive_structs_format_their_parents() {
+    let node = NodeError {
+        name: "leaf",
+        parent: Some(Box::new(NodeError {
+            name: "branch",
+            parent: Some(Box::new(NodeError {
+                name: "root",
+                parent: None,
+            })),
+        })),
+    };
+    assert_eq!(node.to_string(), "leaf in branch in root");
+
+    let node = GenericNodeError(1, "root", vec![GenericNodeError(0, "leaf", Vec::new())]);
+    assert_eq!(node.to_string(), "\"root\" (1 children)");
+}